@@ -5,11 +5,12 @@
 mod config;
 use config::AppConfig;
 use tokio::sync::Mutex;
+mod openapi;
 mod validate;
 use axum::{
     extract::{Path, Query, State},
     http::{header, StatusCode},
-    response::{IntoResponse, Json},
+    response::{IntoResponse, Json, Response},
     routing::{get, post},
     Form, Router,
 };
@@ -47,6 +48,7 @@ use ssi::{crypto::Algorithm, dids, verification_methods};
 use std::{collections::HashMap, fs, net::SocketAddr, sync::Arc};
 use tower_http::validate_request::ValidateRequestHeaderLayer;
 use url::Url;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 use validate::validate;
 
@@ -66,42 +68,71 @@ struct AppState {
     data_cache: DataCache,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct AuthRequestCreateParams {
+    /// Nonce value that becomes part of the authorization request sent to the client
+    #[param(example = "RBx289XF4nCK9Cu6Tx08efcBELwjqQYc6HNORJCE")]
     nonce: String,
 }
 
 /// Authorization Request Object Response.
 ///
 /// See <https://openid.net/specs/openid-4-verifiable-presentations-1_0-20.html#name-cross-device-flow>
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct AuthRequestObjectResponse {
+    /// Nonce value that has been passed when the authorization request was created
     nonce: String,
+    /// Submitted Verifiable Presentation
+    #[schema(value_type = Option<Object>)]
     vp_token: Option<VpToken>,
+    /// Presentation Submission that describes how to decode the presentation
+    #[schema(value_type = Option<Object>)]
     presentation_submission: Option<PresentationSubmission>,
+    /// Status information about the state of the request, e.g. `{"Complete": {"Success": {"info": {}}}}`
+    #[schema(value_type = Object)]
     status: Status,
 }
 
 /// Authorization Request Submission.
 ///
 /// See <https://openid.net/specs/openid-4-verifiable-presentations-1_0-20.html#name-response-mode-direct_post>
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 struct AuthRequestSubmission {
+    /// Submitted Verifiable Presentation
+    #[schema(value_type = String)]
     vp_token: VpToken,
     // presentation_submission: PresentationSubmission,
     // presentation_submission: serde_json::Value,
+    /// JSON encoded Presentation Submission, see
+    /// <https://openid.net/specs/openid-4-verifiable-presentations-1_0-20.html#section-6.2>
     presentation_submission: String,
 }
 
 /// Authorization Request URI Response.
 ///
 /// See <https://openid.net/specs/openid-4-verifiable-presentations-1_0-20.html#name-cross-device-flow>
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct AuthRequestURIResponse {
+    /// Id of the authorization request
+    #[schema(example = "777cab68-3666-4bcb-be11-8f264dc6fa1b")]
     id: Uuid,
+    /// URL to respond to the request, usually passed to wallet via QRCode or link.
+    #[schema(
+        example = "openid4vp://?client_id=did:web:example.com&request_uri=https://example.com/v1/authorize/777cab68-3666-4bcb-be11-8f264dc6fa1b"
+    )]
     url: Url,
 }
 
+/// Redirect to the callback URL after a successful data submission.
+// Mirrors `PostRedirection` for the OpenAPI specification.
+#[derive(ToSchema)]
+#[expect(dead_code, reason = "Only used for the OpenAPI specification")]
+struct PostRedirectionResponse {
+    #[schema(example = "https://client.example.org/callback#response_code=091535f699ea575c7937fa5f0f454aee")]
+    redirect_uri: Url,
+}
+
 // Share data cache that stores data submitted to the service for future retrieval.
 type DataCache = Arc<Mutex<HashMap<Uuid, DataEntry>>>;
 
@@ -286,7 +317,20 @@ fn build_presentation_definition() -> presentation_definition::PresentationDefin
     pres_definition
 }
 
-/// Retrieves the OpenID4VP Authorization Request and sends it to the wallet.
+/// Get Authorization Request
+///
+/// Returns the OpenID4VP Authorization Request. Usually used by the wallet.
+#[utoipa::path(
+    get,
+    path = "/v1/authorize/{requestId}",
+    tag = "openid4vp-wallet",
+    operation_id = "getRequest",
+    params(("requestId" = Uuid, Path, description = "Authorization Request ID")),
+    responses(
+        (status = 200, description = "Returns Authorization Request, see https://openid.net/specs/openid-4-verifiable-presentations-1_0-20.html#name-authorization-request",
+            body = String, content_type = "application/jwt"),
+    )
+)]
 async fn authorize_get(State(state): State<AppState>, Path(request_id): Path<Uuid>) -> impl IntoResponse {
     // println!("authorize_get {request_id}");
     let status = state.verifier.poll_status(request_id).await.unwrap();
@@ -298,9 +342,23 @@ async fn authorize_get(State(state): State<AppState>, Path(request_id): Path<Uui
     (StatusCode::OK, [(header::CONTENT_TYPE, "application/jwt")], auth_request)
 }
 
-/// Accepts data for this Authorization Request. Data can be submitted only once!
+/// Submit data to Authorization Request
 ///
-/// See <https://openid.net/specs/openid-4-verifiable-presentations-1_0-20.html#name-response-mode-direct_post>
+/// Accepts data for this Authorization Request. Data can be submitted only once! See
+/// <https://openid.net/specs/openid-4-verifiable-presentations-1_0-20.html#name-response-mode-direct_post>
+#[utoipa::path(
+    post,
+    path = "/v1/authorize/{requestId}",
+    tag = "openid4vp-wallet",
+    operation_id = "submitResponse",
+    params(("requestId" = Uuid, Path, description = "Authorization Request ID")),
+    request_body(content = AuthRequestSubmission, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Data submission successful. Returns redirect to callback URL.",
+            body = PostRedirectionResponse),
+        (status = 400, description = "Bad Request."),
+    )
+)]
 async fn authorize_submit(
     State(state): State<AppState>,
     Path(request_id): Path<Uuid>,
@@ -363,9 +421,23 @@ async fn authorize_submit(
     (StatusCode::OK, Json(PostRedirection { redirect_uri }))
 }
 
-/// Create Authorization Request.
+/// Create Authorization Request
 ///
-/// See <https://openid.net/specs/openid-4-verifiable-presentations-1_0-20.html#name-authorization-request>
+/// Creates an OpenID4VP Authorization Request and returns the Authorization Request's transaction ID and request URI
+/// for submitting the response. The authorization URI needs to be extended with the nonce generated on the side of the
+/// verifier. See <https://openid.net/specs/openid-4-verifiable-presentations-1_0-20.html#name-authorization-request>
+#[utoipa::path(
+    post,
+    path = "/v1/authrequests",
+    tag = "openid4vp-verifier",
+    operation_id = "createRequest",
+    params(AuthRequestCreateParams),
+    responses(
+        (status = 201, description = "Session created, returns session details.", body = AuthRequestURIResponse),
+        (status = 401, description = "Unauthorized."),
+    ),
+    security(("bearer" = []))
+)]
 async fn authrequest_create(
     State(state): State<AppState>,
     params: Query<AuthRequestCreateParams>,
@@ -468,16 +540,33 @@ async fn authrequest_create(
     (StatusCode::CREATED, Json(AuthRequestURIResponse { id, url }))
 }
 
-// Retrieves the submitted OpenID4VP data and deletes the request from the service.
-async fn authrequest_get(
-    State(state): State<AppState>,
-    Path(request_id): Path<Uuid>,
-) -> (StatusCode, Json<AuthRequestObjectResponse>) {
+/// Get submitted data
+///
+/// Returns the data submitted to the OpenID4VP Authorization Request and the verification result. The request is
+/// deleted from the service once the submitted data has been returned.
+#[utoipa::path(
+    get,
+    path = "/v1/authrequests/{requestId}",
+    tag = "openid4vp-verifier",
+    operation_id = "getResponse",
+    params(("requestId" = Uuid, Path, description = "Authorization Request ID")),
+    responses(
+        (status = 200, description = "Submitted data and verification result.", body = AuthRequestObjectResponse),
+        (status = 204, description = "No content has been submitted, yet."),
+        (status = 401, description = "Unauthorized."),
+    ),
+    security(("bearer" = []))
+)]
+async fn authrequest_get(State(state): State<AppState>, Path(request_id): Path<Uuid>) -> Response {
     // println!("authrequest_get");
     let status = state.verifier.poll_status(request_id).await.unwrap();
     // state.session_store.remove_session(request_id).await.unwrap();
     let mut cache = state.data_cache.lock().await;
     let entry = cache.get(&request_id).unwrap().clone();
+    if entry.vp_token.is_none() {
+        // Nothing has been submitted, yet. Keep the request so that it can be polled again
+        return StatusCode::NO_CONTENT.into_response();
+    }
     // Cleanup, the data is only accessible once
     cache.remove(&request_id);
     drop(cache);
@@ -491,6 +580,7 @@ async fn authrequest_get(
             status,
         }),
     )
+        .into_response()
 }
 
 pub async fn create_app(config: AppConfig) -> Router {
@@ -513,6 +603,7 @@ pub async fn create_app(config: AppConfig) -> Router {
     };
     Router::new()
         .route("/_status/healthz", get(health_check))
+        .route("/openapi.json", get(openapi::openapi_json))
         .nest(
             "/v1",
             Router::new()
@@ -594,10 +685,8 @@ mod tests {
 
     use super::*; // for `call`, `oneshot`, and `ready`
 
-    #[tokio::test]
-    async fn test_initiate_session() {
-        // Create app
-        let app = create_app(AppConfig {
+    fn test_config() -> AppConfig {
+        AppConfig {
             host: "::1".into(),
             port: 3000,
             external_hostname: "localhost".into(),
@@ -606,7 +695,13 @@ mod tests {
             verification_method: "did:jwk:eyJjcnYiOiJQLTI1NiIsImt0eSI6IkVDIiwieCI6ImtYSVJicEtzTzZXZVJ1YndndWdSMWc2RGNhT3NBbmlrVXJ1WXU2QS1HVWMiLCJ5IjoiMG5WdUQ2TkhQeUFEOGF2OWdzM1h6NEoxT2c1ZEFNZDkzdTE1a0RwZklObyJ9#0".into(),
             callback_base_path: "callback".into(),
             bearer_token: None,
-        }).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initiate_session() {
+        // Create app
+        let app = create_app(test_config()).await;
 
         // Create test request
         let request = Request::builder()
@@ -628,4 +723,26 @@ mod tests {
         // Verify that the session_id is a valid UUID
         assert!(Uuid::parse_str(&response.id.to_string()).is_ok());
     }
+
+    #[tokio::test]
+    async fn test_pending_submission() {
+        let app = create_app(test_config()).await;
+
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/v1/authrequests?nonce={nonce}", nonce = Uuid::new_v4()))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let response: AuthRequestURIResponse = serde_json::from_slice(&body).unwrap();
+
+        // Nothing has been submitted, yet. Polling again must be possible
+        for _ in 0..2 {
+            let request =
+                Request::builder().uri(format!("/v1/authrequests/{id}", id = response.id)).body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        }
+    }
 }
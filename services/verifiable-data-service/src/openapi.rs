@@ -0,0 +1,86 @@
+use axum::Json;
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Verifiable Data Service",
+        description = "Verifiable Data Service",
+        license(name = "Apache-2.0"),
+        contact(name = "identinet GmbH", url = "https://identinet.io", email = "support@identinet.io"),
+    ),
+    servers(
+        (url = "http://localhost:3020", description = "Local development server"),
+        (url = "https://demo-shop.vds.check.identinet.io", description = "Demo shop deployment"),
+    ),
+    tags(
+        (name = "openid4vp-wallet", description = "OpenID4VP Operations used by Mobile Identity Wallets"),
+        (name = "openid4vp-verifier", description = "OpenID4VP Operations used by Verifiers"),
+    ),
+    paths(
+        crate::authrequest_create,
+        crate::authrequest_get,
+        crate::authorize_get,
+        crate::authorize_submit,
+    ),
+    modifiers(&BearerToken)
+)]
+pub struct ApiDoc;
+
+/// Registers the client bearer token that protects the verifier operations.
+struct BearerToken;
+
+impl Modify for BearerToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(
+                    HttpBuilder::new().scheme(HttpAuthScheme::Bearer).description(Some("Client bearer token")).build(),
+                ),
+            );
+        }
+    }
+}
+
+/// Returns the OpenAPI specification of the service.
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Comment that is prepended to the generated `openapi.yaml`.
+    const YAML_HEADER: &str = concat!(
+        "# Generated from code, do not edit. Update with: UPDATE_OPENAPI=1 cargo test openapi\n",
+        "# OpenAPI Specification Explained: https://learn.openapis.org/specification/\n",
+        "# Documentation: https://spec.openapis.org/oas/v3.1.1.html\n",
+        "# Graphical editor and test service: https://editor-next.swagger.io/\n",
+    );
+
+    /// Renders the OpenAPI specification as it is committed in `openapi.yaml`.
+    fn openapi_yaml() -> String {
+        let yaml = ApiDoc::openapi().to_yaml().expect("OpenAPI specification can be serialized");
+        format!("{YAML_HEADER}{yaml}")
+    }
+
+    /// Fails when the committed `openapi.yaml` and the specification derived from code diverge.
+    #[test]
+    fn openapi_yaml_is_up_to_date() {
+        let generated = openapi_yaml();
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write("openapi.yaml", &generated).unwrap();
+            return;
+        }
+        assert_eq!(
+            include_str!("../openapi.yaml"),
+            generated,
+            "openapi.yaml is outdated, update it with: UPDATE_OPENAPI=1 cargo test openapi"
+        );
+    }
+}
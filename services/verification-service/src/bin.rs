@@ -1,13 +1,56 @@
 // Fail build if feature is requsted, see https://www.reddit.com/r/rust/comments/8oz7md/make_cargo_fail_on_warning/
 #![cfg_attr(feature = "fail-on-warnings", deny(warnings))]
+// The nested futures of the verification exceed the default limit when their layout is computed
+#![recursion_limit = "256"]
 
 use axum::routing::get;
 use axum::Router;
-use core::net::SocketAddr;
-use std::env;
+use clap::{Parser, Subcommand};
+use core::{net::SocketAddr, time::Duration};
+use std::{env, fs, path::Path, process::ExitCode, sync::Arc};
+use verification_service::cache::{CacheBackend, DiskBackend, RedisBackend};
+use verification_service::concurrency::{set_max_concurrency, DEFAULT_MAX_CONCURRENCY};
+use verification_service::consistency::ConsistencyConfig;
+use verification_service::dns::StubResolver;
+use verification_service::dto::{normalize_query, set_production_mode};
+use verification_service::fetch::{FetchConfig, FetchError, Fetcher};
+use verification_service::http_cache::HttpCache;
+use verification_service::impersonation::ImpersonationConfig;
+use verification_service::lists::AccessLists;
+use verification_service::policy::Policies;
+use verification_service::resolver::DidResolver;
+use verification_service::trust::TrustConfig;
+use verification_service::warnings::WarningConfig;
 
+mod diagnostics;
+mod openapi;
 mod verifications;
 
+use verifications::cache::{CacheConfig, VerificationCache};
+use verifications::service::{CheckConfig, DiscoveryConfig};
+
+#[derive(Parser)]
+#[command(
+    name = env!("CARGO_PKG_NAME"),         // Gets package name from Cargo.toml
+    version = env!("CARGO_PKG_VERSION"),   // Gets version from Cargo.toml
+    about = env!("CARGO_PKG_DESCRIPTION"), // Gets description from Cargo.toml
+    long_about = concat!(env!("CARGO_PKG_DESCRIPTION"), "\n\n", "Without a command, the HTTP server is started. The server is configured via the environment variables HOST, PORT, PRODUCTION, MAX_CONCURRENCY, CACHE_*, CONSISTENCY_*, DISCOVERY_*, FETCH_*, IMPERSONATION_*, LISTS_PATH, POLICIES_PATH, PROTECTED_DOMAINS, TRUSTED_ISSUERS and WARN_*, see README.md.")
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs every discovery step for the given URL or DID and prints the findings with remediation hints as JSON.
+    /// Exits with a non-zero status if errors have been found.
+    Diagnose {
+        /// Domain name, HTTPS URL or DID to diagnose, e.g. example.com
+        q: String,
+    },
+}
+
 // Function to get configuration from environment variables with defaults
 fn get_config() -> (String, u16) {
     let host = env::var("HOST").unwrap_or_else(|_| "::".to_owned());
@@ -23,22 +66,337 @@ fn get_config() -> (String, u16) {
     (host, port)
 }
 
+// Production mode is enabled if the environment variable PRODUCTION is set to `true` or `1`
+fn get_production_mode() -> bool {
+    env::var("PRODUCTION").is_ok_and(|p| p == "true" || p == "1")
+}
+
+// Splits a comma-separated environment variable, e.g. FETCH_ALLOW_LIST=localhost,127.0.0.1
+fn get_list(name: &str) -> Vec<String> {
+    env::var(name)
+        .map(|values| {
+            values
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Configuration of outbound requests via the environment variables FETCH_*
+fn get_fetch_config() -> Result<FetchConfig, String> {
+    let mut config = FetchConfig::default().with_allow_list(get_list("FETCH_ALLOW_LIST"));
+    if let Ok(user_agent) = env::var("FETCH_USER_AGENT") {
+        config.user_agent = user_agent;
+    }
+    if let Ok(proxy) = env::var("FETCH_PROXY") {
+        config = config.with_proxy(&proxy).map_err(|e| e.to_string())?;
+    }
+    for path in get_list("FETCH_ROOT_CERTIFICATES") {
+        let pem = fs::read(&path).map_err(|e| format!("{path}: {e}"))?;
+        config = config
+            .with_root_certificates_pem(&pem)
+            .map_err(|e| format!("{path}: {e}"))?;
+    }
+    config.http2 = env::var("FETCH_HTTP2").map_or(true, |http2| http2 != "false" && http2 != "0");
+    if let Ok(cache_size) = env::var("FETCH_CACHE_SIZE") {
+        config.cache_size = cache_size
+            .parse()
+            .map_err(|e| format!("FETCH_CACHE_SIZE: {e}"))?;
+    }
+    Ok(config)
+}
+
+// Maximum number of concurrent outbound requests and proof verifications via the environment variable MAX_CONCURRENCY
+fn get_max_concurrency() -> Result<usize, String> {
+    env::var("MAX_CONCURRENCY").map_or(Ok(DEFAULT_MAX_CONCURRENCY), |max| {
+        max.parse().map_err(|e| format!("MAX_CONCURRENCY: {e}"))
+    })
+}
+
+// Verification cache via the environment variables CACHE_TTL and CACHE_NEGATIVE_TTL in seconds and CACHE_MAX_SIZE
+fn get_cache_config() -> Result<CacheConfig, String> {
+    let mut config = CacheConfig::default();
+    if let Ok(ttl) = env::var("CACHE_TTL") {
+        config.ttl = Duration::from_secs(ttl.parse().map_err(|e| format!("CACHE_TTL: {e}"))?);
+    }
+    if let Ok(ttl) = env::var("CACHE_NEGATIVE_TTL") {
+        config.negative_ttl = Duration::from_secs(
+            ttl.parse()
+                .map_err(|e| format!("CACHE_NEGATIVE_TTL: {e}"))?,
+        );
+    }
+    if let Ok(max_size) = env::var("CACHE_MAX_SIZE") {
+        config.max_size = max_size
+            .parse()
+            .map_err(|e| format!("CACHE_MAX_SIZE: {e}"))?;
+    }
+    Ok(config)
+}
+
+// Flags are enabled if the environment variable is set to `true` or `1`
+fn get_flag(name: &str) -> bool {
+    env::var(name).is_ok_and(|p| p == "true" || p == "1")
+}
+
+// Discovery strategies via the environment variables DISCOVERY_DID_WEB_PATH, DISCOVERY_HTML and DISCOVERY_DNS*. DNS
+// queries are sent to DISCOVERY_DNS_SERVER or the first name server of /etc/resolv.conf
+fn get_discovery_config() -> Result<DiscoveryConfig, String> {
+    let mut config = DiscoveryConfig {
+        did_web_path: get_flag("DISCOVERY_DID_WEB_PATH"),
+        dns_require_dnssec: get_flag("DISCOVERY_DNS_REQUIRE_DNSSEC"),
+        html: env::var("DISCOVERY_HTML").map_or(true, |html| html != "false" && html != "0"),
+        ..DiscoveryConfig::default()
+    };
+    if get_flag("DISCOVERY_DNS") {
+        let resolver = match env::var("DISCOVERY_DNS_SERVER") {
+            Ok(server) => {
+                let server = server
+                    .parse()
+                    .or_else(|_| server.parse().map(|ip| SocketAddr::new(ip, 53)))
+                    .map_err(|e| format!("DISCOVERY_DNS_SERVER: {e}"))?;
+                StubResolver::new(server)
+            }
+            Err(_) => StubResolver::from_system_config().map_err(|e| e.to_string())?,
+        };
+        config.dns = Some(Arc::new(resolver));
+    }
+    Ok(config)
+}
+
+// Warning thresholds via the environment variables WARN_EXPIRY_DAYS and WARN_LEGACY_PROOF_TYPES
+fn get_warning_config() -> Result<WarningConfig, String> {
+    let mut config = WarningConfig::default();
+    if let Ok(days) = env::var("WARN_EXPIRY_DAYS") {
+        let days: u64 = days.parse().map_err(|e| format!("WARN_EXPIRY_DAYS: {e}"))?;
+        config = config.with_expiry_window(Duration::from_secs(days * 24 * 60 * 60));
+    }
+    if env::var("WARN_LEGACY_PROOF_TYPES").is_ok() {
+        config.legacy_proof_types = get_list("WARN_LEGACY_PROOF_TYPES");
+    }
+    Ok(config)
+}
+
+// Consistency checks via the environment variables CONSISTENCY_CLAIM_CONFLICTS and CONSISTENCY_ORIGIN_MISMATCH:
+// `ignore`, `warn` or `fail`
+fn get_consistency_config() -> Result<ConsistencyConfig, String> {
+    let mut config = ConsistencyConfig::default();
+    if let Ok(action) = env::var("CONSISTENCY_CLAIM_CONFLICTS") {
+        config.claim_conflicts = action
+            .parse()
+            .map_err(|e| format!("CONSISTENCY_CLAIM_CONFLICTS: {e}"))?;
+    }
+    if let Ok(action) = env::var("CONSISTENCY_ORIGIN_MISMATCH") {
+        config.origin_mismatch = action
+            .parse()
+            .map_err(|e| format!("CONSISTENCY_ORIGIN_MISMATCH: {e}"))?;
+    }
+    Ok(config)
+}
+
+// Lookalike detection via the environment variables PROTECTED_DOMAINS, IMPERSONATION_MAX_DISTANCE and
+// IMPERSONATION_OVERRIDE
+fn get_impersonation_config() -> Result<ImpersonationConfig, String> {
+    let mut config =
+        ImpersonationConfig::default().with_protected_domains(get_list("PROTECTED_DOMAINS"));
+    if let Ok(distance) = env::var("IMPERSONATION_MAX_DISTANCE") {
+        config.max_distance = distance
+            .parse()
+            .map_err(|e| format!("IMPERSONATION_MAX_DISTANCE: {e}"))?;
+    }
+    config.override_verified =
+        env::var("IMPERSONATION_OVERRIDE").map_or(true, |value| value != "false" && value != "0");
+    Ok(config)
+}
+
+// Allow and block lists via the environment variable LISTS_PATH, a JSON file that is reloaded when it changes
+fn get_lists() -> Result<AccessLists, String> {
+    match env::var("LISTS_PATH") {
+        Ok(path) => AccessLists::load(Path::new(&path)).map_err(|e| format!("LISTS_PATH: {e}")),
+        Err(_) => Ok(AccessLists::default()),
+    }
+}
+
+// Policies via the environment variable POLICIES_PATH, a directory of TOML and JSON files that are named after the
+// policy
+fn get_policies() -> Result<Policies, String> {
+    match env::var("POLICIES_PATH") {
+        Ok(path) => Policies::load(Path::new(&path)).map_err(|e| format!("POLICIES_PATH: {e}")),
+        Err(_) => Ok(Policies::default()),
+    }
+}
+
+// Checks of the verification results via the environment variables WARN_*, TRUSTED_ISSUERS, CONSISTENCY_*,
+// IMPERSONATION_*, PROTECTED_DOMAINS, LISTS_PATH and POLICIES_PATH
+fn get_check_config() -> Result<CheckConfig, String> {
+    Ok(CheckConfig {
+        warnings: get_warning_config()?,
+        trust: TrustConfig::default().with_trusted_issuers(get_list("TRUSTED_ISSUERS")),
+        consistency: get_consistency_config()?,
+        impersonation: get_impersonation_config()?,
+        lists: get_lists()?,
+        policies: get_policies()?,
+    })
+}
+
+// Cache backend via the environment variable CACHE_BACKEND: `memory`, `disk` with the file CACHE_PATH or `redis` with the
+// server CACHE_REDIS_URL
+async fn get_cache_backend() -> Result<Option<Arc<dyn CacheBackend>>, String> {
+    let backend: Arc<dyn CacheBackend> = match env::var("CACHE_BACKEND").as_deref() {
+        Err(_) | Ok("memory") => return Ok(None),
+        Ok("disk") => {
+            let path =
+                env::var("CACHE_PATH").unwrap_or_else(|_| "verification-cache.redb".to_owned());
+            Arc::new(DiskBackend::open(Path::new(&path)).map_err(|e| e.to_string())?)
+        }
+        Ok("redis") => {
+            let url =
+                env::var("CACHE_REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_owned());
+            Arc::new(
+                RedisBackend::connect(&url)
+                    .await
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        Ok(backend) => return Err(format!("CACHE_BACKEND: unknown backend '{backend}'")),
+    };
+    Ok(Some(backend))
+}
+
+/// State shared by all request handlers.
+#[derive(Clone)]
+pub struct AppState {
+    /// Limits of outbound requests.
+    pub fetch_config: FetchConfig,
+    /// Fetcher with the HTTP client that is shared by all requests.
+    pub fetcher: Fetcher,
+    /// DID resolver that downloads did:web documents with the shared HTTP client.
+    pub resolver: DidResolver,
+    /// Outcomes of recent verifications.
+    pub cache: VerificationCache,
+    /// Strategies to discover the DIDs of a URL.
+    pub discovery: DiscoveryConfig,
+    /// Checks and grading that are applied to the verification results.
+    pub checks: CheckConfig,
+}
+
+impl AppState {
+    fn new(
+        fetch_config: FetchConfig,
+        cache_config: CacheConfig,
+        discovery: DiscoveryConfig,
+        checks: CheckConfig,
+    ) -> Result<Self, FetchError> {
+        let mut fetcher = Fetcher::new(fetch_config.clone())?;
+        if let Some(backend) = &cache_config.backend {
+            fetcher = fetcher.with_cache(HttpCache::with_backend(Arc::clone(backend)));
+        }
+        let resolver = DidResolver::default().with_fetcher(fetcher.clone());
+        Ok(Self {
+            fetch_config,
+            fetcher,
+            resolver,
+            cache: VerificationCache::new(cache_config),
+            discovery,
+            checks,
+        })
+    }
+}
+
 /// Liveness check
 async fn health_check() -> String {
     "Ok".to_owned()
 }
 
-pub fn create_app() -> Router {
+pub fn create_app(state: AppState) -> Router {
     let verifications_router = verifications::create_router();
-    let v1_router = Router::new().merge(verifications_router);
+    let diagnostics_router = diagnostics::create_router();
+    let v1_router = Router::new()
+        .merge(verifications_router)
+        .merge(diagnostics_router);
     Router::new()
         .route("/_status/healthz", get(health_check))
+        .route("/openapi.json", get(openapi::openapi_json))
         .nest("/v1", v1_router)
+        .nest("/v2", verifications::create_router_v2())
+        .with_state(state)
+}
+
+/// Prints the diagnosis of the given URL or DID to stdout.
+async fn diagnose(q: &str, fetch_config: &FetchConfig, warnings: &WarningConfig) -> ExitCode {
+    let Some(url) = normalize_query(q) else {
+        eprintln!("invalid URL or DID: {q}");
+        return ExitCode::from(2);
+    };
+    let resolver = match Fetcher::new(fetch_config.clone()) {
+        Ok(fetcher) => DidResolver::default().with_fetcher(fetcher),
+        Err(e) => {
+            eprintln!("HTTP client could not be initialized: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let diagnosis = diagnostics::service::diagnose(&url, fetch_config, &resolver, warnings).await;
+    println!("{}", serde_json::to_string_pretty(&diagnosis).unwrap());
+    if diagnosis.passed {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
 }
 
 #[tokio::main]
-async fn main() {
-    let app = create_app();
+async fn main() -> ExitCode {
+    let (fetch_config, max_concurrency, mut cache_config, discovery, checks) =
+        match get_fetch_config().and_then(|config| {
+            Ok((
+                config,
+                get_max_concurrency()?,
+                get_cache_config()?,
+                get_discovery_config()?,
+                get_check_config()?,
+            ))
+        }) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("invalid configuration: {e}");
+                return ExitCode::from(2);
+            }
+        };
+    // nothing has been fetched yet, thus the default limit can't be in effect already
+    let _ = set_max_concurrency(max_concurrency);
+    if let Some(Command::Diagnose { q }) = Cli::parse().command {
+        return diagnose(&q, &fetch_config, &checks.warnings).await;
+    }
+
+    set_production_mode(get_production_mode());
+    // the lists are reloaded as long as the watcher is alive
+    let _watcher = match env::var("LISTS_PATH") {
+        Ok(path) => match checks.lists.watch(Path::new(&path)) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                eprintln!("lists could not be watched: {e}");
+                return ExitCode::FAILURE;
+            }
+        },
+        Err(_) => None,
+    };
+    cache_config.backend = match get_cache_backend().await {
+        Ok(backend) => backend,
+        Err(e) => {
+            eprintln!("cache backend could not be opened: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let state = match AppState::new(fetch_config, cache_config, discovery, checks) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("HTTP client could not be initialized: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let app = create_app(state);
 
     let (host, port) = get_config();
     let addr = format!("{host}:{port}")
@@ -48,4 +406,5 @@ async fn main() {
     println!("Listening on {addr}");
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
+    ExitCode::SUCCESS
 }
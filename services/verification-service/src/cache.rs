@@ -0,0 +1,468 @@
+use core::{fmt, future::Future, pin::Pin, time::Duration};
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use moka::{future::Cache, Expiry};
+use redb::{Database, TableDefinition};
+use redis::aio::ConnectionManager;
+
+/// Cache error.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CacheError {
+    /// The backend couldn't be opened, e.g. the server is unreachable or the file is locked
+    #[error("Cache backend unavailable: {0}")]
+    Unavailable(String),
+
+    /// Reading or writing a value failed
+    #[error("Cache backend failed: {0}")]
+    Backend(String),
+}
+
+/// Future that is returned by the operations of a [`CacheBackend`].
+pub type CacheFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, CacheError>> + Send + 'a>>;
+
+/// Storage of cached values, e.g. verification results and fetched documents.
+///
+/// Values are opaque bytes that expire after the TTL they have been stored with. Backends may evict values earlier,
+/// e.g. when they run out of space. Values with a zero TTL are not stored.
+pub trait CacheBackend: fmt::Debug + Send + Sync {
+    /// Returns the value of the key, `None` if it doesn't exist or has expired.
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Vec<u8>>>;
+
+    /// Stores the value of the key for the given time.
+    fn set<'a>(&'a self, key: &'a str, value: Vec<u8>, ttl: Duration) -> CacheFuture<'a, ()>;
+
+    /// Removes the value of the key.
+    fn remove<'a>(&'a self, key: &'a str) -> CacheFuture<'a, ()>;
+}
+
+/// Value of the [`MemoryBackend`] with the time it has been stored for.
+#[derive(Clone)]
+struct MemoryEntry {
+    value: Arc<[u8]>,
+    ttl: Duration,
+}
+
+/// Evicts entries of the [`MemoryBackend`] after their TTL.
+struct EntryTtl;
+
+impl Expiry<String, MemoryEntry> for EntryTtl {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        entry: &MemoryEntry,
+        _created_at: std::time::Instant,
+    ) -> Option<Duration> {
+        Some(entry.ttl)
+    }
+}
+
+/// Size-bounded cache in the memory of the process. Values aren't shared with other replicas and are lost on restart.
+#[derive(Clone)]
+pub struct MemoryBackend {
+    entries: Cache<String, MemoryEntry>,
+}
+
+impl MemoryBackend {
+    /// Creates a backend that stores up to `max_size` bytes. The least recently used values are evicted first.
+    #[inline]
+    #[must_use]
+    pub fn new(max_size: u64) -> Self {
+        let entries = Cache::builder()
+            .max_capacity(max_size)
+            .weigher(|key: &String, entry: &MemoryEntry| {
+                u32::try_from(key.len() + entry.value.len()).unwrap_or(u32::MAX)
+            })
+            .expire_after(EntryTtl)
+            .build();
+        Self { entries }
+    }
+}
+
+impl fmt::Debug for MemoryBackend {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBackend")
+            .field("entries", &self.entries.entry_count())
+            .finish()
+    }
+}
+
+impl CacheBackend for MemoryBackend {
+    #[inline]
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Vec<u8>>> {
+        Box::pin(async move {
+            Ok(self
+                .entries
+                .get(key)
+                .await
+                .map(|entry| entry.value.to_vec()))
+        })
+    }
+
+    #[inline]
+    fn set<'a>(&'a self, key: &'a str, value: Vec<u8>, ttl: Duration) -> CacheFuture<'a, ()> {
+        Box::pin(async move {
+            if ttl.is_zero() {
+                self.entries.invalidate(key).await;
+            } else {
+                let entry = MemoryEntry {
+                    value: value.into(),
+                    ttl,
+                };
+                self.entries.insert(key.to_owned(), entry).await;
+            }
+            Ok(())
+        })
+    }
+
+    #[inline]
+    fn remove<'a>(&'a self, key: &'a str) -> CacheFuture<'a, ()> {
+        Box::pin(async move {
+            self.entries.invalidate(key).await;
+            Ok(())
+        })
+    }
+}
+
+/// Table of the [`DiskBackend`]. Values are prefixed with their expiry in milliseconds since the Unix epoch.
+const DISK_TABLE: TableDefinition<'_, &str, &[u8]> = TableDefinition::new("cache");
+
+/// Persistent cache in an embedded key-value store on disk. Values survive restarts, the file must not be shared by
+/// several processes.
+#[derive(Clone, Debug)]
+pub struct DiskBackend {
+    database: Arc<Database>,
+}
+
+impl DiskBackend {
+    /// Opens or creates the store at the given path and removes expired values.
+    #[inline]
+    pub fn open(path: &Path) -> Result<Self, CacheError> {
+        let database = Database::create(path)
+            .map_err(|e| CacheError::Unavailable(format!("{}: {e}", path.display())))?;
+        let backend = Self {
+            database: Arc::new(database),
+        };
+        backend.purge_expired()?;
+        Ok(backend)
+    }
+
+    /// Removes all expired values.
+    fn purge_expired(&self) -> Result<(), CacheError> {
+        let now = unix_millis(SystemTime::now());
+        let transaction = self.database.begin_write().map_err(backend_error)?;
+        {
+            let mut table = transaction.open_table(DISK_TABLE).map_err(backend_error)?;
+            table
+                .retain(|_, value| expires_at(value).is_some_and(|expires_at| expires_at > now))
+                .map_err(backend_error)?;
+        }
+        transaction.commit().map_err(backend_error)
+    }
+
+    /// Runs the blocking operation on the store without blocking the runtime.
+    async fn run<T, F>(&self, operation: F) -> Result<T, CacheError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Database) -> Result<T, CacheError> + Send + 'static,
+    {
+        let database = Arc::clone(&self.database);
+        tokio::task::spawn_blocking(move || operation(&database))
+            .await
+            .map_err(backend_error)?
+    }
+}
+
+impl CacheBackend for DiskBackend {
+    #[inline]
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Vec<u8>>> {
+        let key = key.to_owned();
+        Box::pin(self.run(move |database| {
+            let transaction = database.begin_read().map_err(backend_error)?;
+            let table = match transaction.open_table(DISK_TABLE) {
+                Ok(table) => table,
+                Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+                Err(e) => return Err(backend_error(e)),
+            };
+            let now = unix_millis(SystemTime::now());
+            Ok(table
+                .get(key.as_str())
+                .map_err(backend_error)?
+                .and_then(|value| {
+                    let value = value.value();
+                    expires_at(value)
+                        .is_some_and(|expires_at| expires_at > now)
+                        .then(|| value[8..].to_vec())
+                }))
+        }))
+    }
+
+    #[inline]
+    fn set<'a>(&'a self, key: &'a str, value: Vec<u8>, ttl: Duration) -> CacheFuture<'a, ()> {
+        if ttl.is_zero() {
+            return self.remove(key);
+        }
+        let key = key.to_owned();
+        Box::pin(self.run(move |database| {
+            let mut stored = unix_millis(SystemTime::now() + ttl).to_be_bytes().to_vec();
+            stored.extend_from_slice(&value);
+            let transaction = database.begin_write().map_err(backend_error)?;
+            transaction
+                .open_table(DISK_TABLE)
+                .map_err(backend_error)?
+                .insert(key.as_str(), stored.as_slice())
+                .map_err(backend_error)?;
+            transaction.commit().map_err(backend_error)
+        }))
+    }
+
+    #[inline]
+    fn remove<'a>(&'a self, key: &'a str) -> CacheFuture<'a, ()> {
+        let key = key.to_owned();
+        Box::pin(self.run(move |database| {
+            let transaction = database.begin_write().map_err(backend_error)?;
+            transaction
+                .open_table(DISK_TABLE)
+                .map_err(backend_error)?
+                .remove(key.as_str())
+                .map_err(backend_error)?;
+            transaction.commit().map_err(backend_error)
+        }))
+    }
+}
+
+/// Returns the expiry of a value of the [`DiskBackend`].
+fn expires_at(value: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(value.get(..8)?.try_into().ok()?))
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    u64::try_from(
+        time.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis(),
+    )
+    .unwrap_or(u64::MAX)
+}
+
+fn backend_error(e: impl fmt::Display) -> CacheError {
+    CacheError::Backend(e.to_string())
+}
+
+/// Cache on a server that speaks the Redis protocol, e.g. Redis or Valkey. Values are shared by all replicas of the
+/// service. The connection is re-established automatically.
+#[derive(Clone)]
+pub struct RedisBackend {
+    connection: ConnectionManager,
+}
+
+impl RedisBackend {
+    /// Connects to the server at the given URL, e.g. `redis://redis:6379/0`.
+    #[inline]
+    pub async fn connect(url: &str) -> Result<Self, CacheError> {
+        let client =
+            redis::Client::open(url).map_err(|e| CacheError::Unavailable(e.to_string()))?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .map_err(|e| CacheError::Unavailable(e.to_string()))?;
+        Ok(Self { connection })
+    }
+}
+
+impl fmt::Debug for RedisBackend {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisBackend").finish_non_exhaustive()
+    }
+}
+
+impl CacheBackend for RedisBackend {
+    #[inline]
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Vec<u8>>> {
+        let mut connection = self.connection.clone();
+        Box::pin(async move {
+            redis::cmd("GET")
+                .arg(key)
+                .query_async(&mut connection)
+                .await
+                .map_err(backend_error)
+        })
+    }
+
+    #[inline]
+    fn set<'a>(&'a self, key: &'a str, value: Vec<u8>, ttl: Duration) -> CacheFuture<'a, ()> {
+        if ttl.is_zero() {
+            return self.remove(key);
+        }
+        let mut connection = self.connection.clone();
+        Box::pin(async move {
+            redis::cmd("SET")
+                .arg(key)
+                .arg(value)
+                .arg("PX")
+                .arg(u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX))
+                .query_async(&mut connection)
+                .await
+                .map_err(backend_error)
+        })
+    }
+
+    #[inline]
+    fn remove<'a>(&'a self, key: &'a str) -> CacheFuture<'a, ()> {
+        let mut connection = self.connection.clone();
+        Box::pin(async move {
+            redis::cmd("DEL")
+                .arg(key)
+                .query_async(&mut connection)
+                .await
+                .map_err(backend_error)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use tokio::{
+        io::{AsyncBufReadExt as _, AsyncReadExt as _, AsyncWriteExt as _, BufReader},
+        net::{tcp::OwnedReadHalf, TcpListener},
+    };
+
+    use super::*;
+
+    /// Stores, reads and removes a value with the given backend.
+    async fn exercise(backend: &dyn CacheBackend) {
+        assert_eq!(backend.get("key").await.unwrap(), None);
+        backend
+            .set("key", b"value".to_vec(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(
+            backend.get("key").await.unwrap().as_deref(),
+            Some(&b"value"[..])
+        );
+        backend.remove("key").await.unwrap();
+        assert_eq!(backend.get("key").await.unwrap(), None);
+        backend
+            .set("key", b"value".to_vec(), Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(backend.get("key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn memory_backend() {
+        let backend = MemoryBackend::new(1024);
+        exercise(&backend).await;
+
+        backend
+            .set("short", b"value".to_vec(), Duration::from_millis(50))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(backend.get("short").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn disk_backend() {
+        let path =
+            std::env::temp_dir().join(format!("verification-cache-{}.redb", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let backend = DiskBackend::open(&path).unwrap();
+            exercise(&backend).await;
+            backend
+                .set("persistent", b"value".to_vec(), Duration::from_secs(60))
+                .await
+                .unwrap();
+            backend
+                .set("short", b"value".to_vec(), Duration::from_millis(50))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert_eq!(backend.get("short").await.unwrap(), None);
+        }
+        // values survive a restart
+        let backend = DiskBackend::open(&path).unwrap();
+        assert_eq!(
+            backend.get("persistent").await.unwrap().as_deref(),
+            Some(&b"value"[..])
+        );
+        drop(backend);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Reads a command in the Redis serialization protocol, i.e. an array of bulk strings.
+    async fn read_command(reader: &mut BufReader<OwnedReadHalf>) -> Option<Vec<Vec<u8>>> {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.ok()?;
+        let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+        let mut arguments = Vec::with_capacity(count);
+        for _ in 0..count {
+            line.clear();
+            reader.read_line(&mut line).await.ok()?;
+            let length: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+            let mut argument = vec![0; length + 2];
+            reader.read_exact(&mut argument).await.ok()?;
+            argument.truncate(length);
+            arguments.push(argument);
+        }
+        Some(arguments)
+    }
+
+    /// Starts a local server that implements the subset of the Redis protocol that is used by the backend.
+    async fn serve_redis() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let values = Arc::new(Mutex::new(HashMap::<Vec<u8>, Vec<u8>>::new()));
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let values = Arc::clone(&values);
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut reader = BufReader::new(reader);
+                    while let Some(command) = read_command(&mut reader).await {
+                        let reply = match command[0].to_ascii_uppercase().as_slice() {
+                            b"GET" => match values.lock().unwrap().get(&command[1]) {
+                                Some(value) => {
+                                    [format!("${}\r\n", value.len()).as_bytes(), value, b"\r\n"]
+                                        .concat()
+                                }
+                                None => b"$-1\r\n".to_vec(),
+                            },
+                            b"SET" => {
+                                values
+                                    .lock()
+                                    .unwrap()
+                                    .insert(command[1].clone(), command[2].clone());
+                                b"+OK\r\n".to_vec()
+                            }
+                            b"DEL" => {
+                                let removed = values.lock().unwrap().remove(&command[1]).is_some();
+                                format!(":{}\r\n", u8::from(removed)).into_bytes()
+                            }
+                            // e.g. CLIENT SETINFO during the connection setup
+                            _ => b"+OK\r\n".to_vec(),
+                        };
+                        if writer.write_all(&reply).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        format!("redis://127.0.0.1:{port}")
+    }
+
+    #[tokio::test]
+    async fn redis_backend() {
+        let backend = RedisBackend::connect(&serve_redis().await).await.unwrap();
+        exercise(&backend).await;
+    }
+}
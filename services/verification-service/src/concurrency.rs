@@ -0,0 +1,108 @@
+use std::sync::OnceLock;
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Default maximum number of concurrent fetch and verification tasks, see [`set_max_concurrency`].
+pub const DEFAULT_MAX_CONCURRENCY: usize = 64;
+
+/// Process-wide limits, shared by all verifications.
+///
+/// Fetches and verifications draw from separate pools because verifying a proof resolves DIDs, i.e. fetches documents,
+/// while holding a verification permit. A single pool could be drained by verifications that wait for fetches.
+struct Limits {
+    max_concurrency: usize,
+    fetches: Semaphore,
+    verifications: Semaphore,
+}
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+
+fn limits() -> &'static Limits {
+    LIMITS.get_or_init(|| Limits::new(DEFAULT_MAX_CONCURRENCY))
+}
+
+impl Limits {
+    fn new(max_concurrency: usize) -> Self {
+        Self {
+            max_concurrency,
+            fetches: Semaphore::new(max_concurrency),
+            verifications: Semaphore::new(max_concurrency),
+        }
+    }
+}
+
+/// Sets the maximum number of outbound requests and the maximum number of proof verifications that are processed at
+/// the same time across all verifications. Further tasks wait until a running task finishes.
+///
+/// The limit must be set before the first verification starts. Fails with the limit in effect if it has been set or
+/// used already.
+#[inline]
+pub fn set_max_concurrency(max_concurrency: usize) -> Result<(), usize> {
+    LIMITS
+        .set(Limits::new(max_concurrency.max(1)))
+        .map_err(|_| limits().max_concurrency)
+}
+
+/// Waits until an outbound request may be sent. The request must complete before the permit is dropped.
+pub(crate) async fn acquire_fetch() -> SemaphorePermit<'static> {
+    limits()
+        .fetches
+        .acquire()
+        .await
+        .expect("semaphore is never closed")
+}
+
+/// Waits until a proof may be verified. The verification of nested credentials must not be started while the permit
+/// is held, otherwise verifications could wait for each other.
+pub(crate) async fn acquire_verification() -> SemaphorePermit<'static> {
+    limits()
+        .verifications
+        .acquire()
+        .await
+        .expect("semaphore is never closed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::time::Duration;
+    use std::sync::Arc;
+    use tokio::task::JoinSet;
+    use tokio::time::Instant;
+
+    #[tokio::test]
+    async fn test_acquire_fetch_bounded() {
+        let max_concurrency = limits().max_concurrency;
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let started = Instant::now();
+        let mut tasks = JoinSet::new();
+        for _ in 0..=max_concurrency {
+            let (running, max_running) = (Arc::clone(&running), Arc::clone(&max_running));
+            tasks.spawn(async move {
+                let _permit = acquire_fetch().await;
+                let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now_running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        tasks.join_all().await;
+        // other tests may hold permits at the same time, thus fewer tasks may have run concurrently
+        assert!(max_running.load(Ordering::SeqCst) <= max_concurrency);
+        // the last task had to wait for a permit
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_set_max_concurrency_after_use() {
+        drop(acquire_fetch().await);
+        let max_concurrency = limits().max_concurrency;
+        // the limit in effect is reported instead
+        assert_eq!(
+            set_max_concurrency(max_concurrency + 1),
+            Err(max_concurrency)
+        );
+    }
+}
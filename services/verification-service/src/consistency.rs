@@ -0,0 +1,381 @@
+use core::{iter, str::FromStr};
+
+use serde_json::Value;
+use ssi::claims::vc::v1::SpecializedJsonCredential;
+use url::Url;
+
+use crate::dto::{Finding, GroupedVerificationResponseDto, VerificationResult};
+use crate::identity::{credential_subjects, property, IdentityClaim, IdentitySummary};
+
+/// Properties of a credential subject that contain the domain the subject operates, e.g. schema.org `url`.
+const DOMAIN: [&str; 4] = ["url", "domain", "domainName", "website"];
+
+/// How a finding of the consistency checks is reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConsistencyAction {
+    /// The check is skipped.
+    Ignore,
+    /// The finding is reported as warning of the credential.
+    #[default]
+    Warn,
+    /// The credential fails with `VcConsistencyError`.
+    Fail,
+}
+
+impl FromStr for ConsistencyAction {
+    type Err = String;
+
+    #[inline]
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action {
+            "ignore" => Ok(Self::Ignore),
+            "warn" => Ok(Self::Warn),
+            "fail" => Ok(Self::Fail),
+            _ => Err(format!(
+                "unknown action '{action}', expected 'ignore', 'warn' or 'fail'"
+            )),
+        }
+    }
+}
+
+/// Policy of the checks that compare the claims of the valid credentials with each other and with the verified
+/// origin.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct ConsistencyConfig {
+    /// Credentials that disagree on the legal name, country, company registration, LEI or VAT ID.
+    pub claim_conflicts: ConsistencyAction,
+    /// Credentials whose `url` or `domain` claims describe another domain than the verified origin.
+    pub origin_mismatch: ConsistencyAction,
+}
+
+/// Code and label of a conflict, the accessor of the claim in the identity summary and its normalization.
+type ClaimCheck = (
+    &'static str,
+    &'static str,
+    fn(&IdentitySummary) -> Option<IdentityClaim>,
+    fn(&str) -> String,
+);
+
+/// Finding of a credential, identified by its index in the order of the grouped result.
+struct Inconsistency {
+    credential: usize,
+    action: ConsistencyAction,
+    code: &'static str,
+    message: String,
+}
+
+impl ConsistencyConfig {
+    /// Compares the claims of the credentials that have been verified successfully.
+    ///
+    /// The claims of the credential that takes precedence in the identity summary are the reference: credentials
+    /// that contradict them are reported with `consistency.<property>_conflict`. Credentials that only describe other
+    /// domains than the verified origin are reported with `consistency.origin_mismatch`. Depending on the policy, the
+    /// findings are added as warnings or the credentials fail.
+    #[inline]
+    pub fn check(&self, dto: &mut GroupedVerificationResponseDto) {
+        let inconsistencies = self.inconsistencies(dto.valid_credentials(), dto.origin.as_deref());
+
+        if inconsistencies.is_empty() {
+            return;
+        }
+        let credentials = dto
+            .dids
+            .iter_mut()
+            .flat_map(|did| {
+                let holder = did.document.id.to_string();
+                did.services
+                    .iter_mut()
+                    .flat_map(|service| &mut service.presentations)
+                    .flat_map(|presentation| &mut presentation.credentials)
+                    .map(move |credential| (holder.clone(), credential))
+            })
+            .enumerate();
+        for (index, (holder, credential)) in credentials {
+            for inconsistency in inconsistencies
+                .iter()
+                .filter(|inconsistency| inconsistency.credential == index)
+            {
+                match inconsistency.action {
+                    ConsistencyAction::Ignore => {}
+                    ConsistencyAction::Warn => {
+                        let resource = credential
+                            .credential
+                            .id
+                            .as_ref()
+                            .map_or_else(|| holder.clone(), ToString::to_string);
+                        credential.warnings.push(Finding::warning(
+                            inconsistency.code,
+                            &resource,
+                            inconsistency.message.clone(),
+                        ));
+                    }
+                    ConsistencyAction::Fail => {
+                        credential.result =
+                            VerificationResult::vc_consistency_error(inconsistency.message.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Compares the claims of the given credentials, given with their index and the DID of their holder.
+    fn inconsistencies<'a>(
+        &self,
+        credentials: impl Iterator<Item = (usize, &'a str, &'a SpecializedJsonCredential)>,
+        origin: Option<&str>,
+    ) -> Vec<Inconsistency> {
+        let mut credentials: Vec<_> = credentials.collect();
+        // same precedence as in the identity summary
+        credentials
+            .sort_by_key(|(_, holder, credential)| credential.issuer.id().as_str() == *holder);
+
+        let mut inconsistencies = Vec::new();
+        if self.claim_conflicts != ConsistencyAction::Ignore {
+            let claims: Vec<_> = credentials
+                .iter()
+                .map(|credential| IdentitySummary::from_credentials(iter::once(*credential)))
+                .collect();
+            let properties: [ClaimCheck; 5] = [
+                (
+                    "consistency.legal_name_conflict",
+                    "Legal name",
+                    |claims| claims.legal_name.clone(),
+                    normalize_name,
+                ),
+                (
+                    "consistency.country_conflict",
+                    "Country",
+                    |claims| {
+                        claims.address.as_ref().and_then(|address| {
+                            Some(IdentityClaim {
+                                source: address.source.clone(),
+                                value: address.value.country.clone()?,
+                            })
+                        })
+                    },
+                    normalize_identifier,
+                ),
+                (
+                    "consistency.registration_number_conflict",
+                    "Company registration",
+                    |claims| claims.registration_number.clone(),
+                    normalize_identifier,
+                ),
+                (
+                    "consistency.lei_conflict",
+                    "LEI",
+                    |claims| claims.lei.clone(),
+                    normalize_identifier,
+                ),
+                (
+                    "consistency.vat_id_conflict",
+                    "VAT ID",
+                    |claims| claims.vat_id.clone(),
+                    normalize_identifier,
+                ),
+            ];
+            for (code, label, claim, normalize) in properties {
+                let mut values = claims.iter().filter_map(claim);
+                let Some(reference) = values.next() else {
+                    continue;
+                };
+                for value in values {
+                    if normalize(&value.value) != normalize(&reference.value) {
+                        inconsistencies.push(Inconsistency {
+                            credential: value.source.credential,
+                            action: self.claim_conflicts,
+                            code,
+                            message: format!(
+                                "{label} '{}' contradicts '{}' of credential {}.",
+                                value.value, reference.value, reference.source.credential
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+
+        let host = origin
+            .and_then(|origin| Url::parse(origin).ok())
+            .and_then(|origin| origin.host_str().map(str::to_owned));
+        if let (Some(host), true) = (host, self.origin_mismatch != ConsistencyAction::Ignore) {
+            for (index, _, credential) in &credentials {
+                let domains = claimed_domains(credential);
+                if !domains.is_empty() && !domains.iter().any(|domain| covers(domain, &host)) {
+                    inconsistencies.push(Inconsistency {
+                        credential: *index,
+                        action: self.origin_mismatch,
+                        code: "consistency.origin_mismatch",
+                        message: format!(
+                            "Credential describes {} instead of {host}.",
+                            domains.join(", ")
+                        ),
+                    });
+                }
+            }
+        }
+        inconsistencies
+    }
+}
+
+/// Normalizes a name for comparison, i.e. ignores case, punctuation and whitespace differences.
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Normalizes an identifier for comparison, e.g. `DE 123.456.789` and `de123456789` are equal.
+fn normalize_identifier(identifier: &str) -> String {
+    identifier
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Returns the domains that the subjects of the credential claim to operate, in punycode.
+pub(crate) fn claimed_domains(credential: &SpecializedJsonCredential) -> Vec<String> {
+    let json = serde_json::to_value(credential).unwrap_or_default();
+    credential_subjects(&json)
+        .into_iter()
+        .filter_map(|subject| property(subject, &DOMAIN))
+        .flat_map(|value| match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        })
+        .filter_map(Value::as_str)
+        .filter_map(domain)
+        .collect()
+}
+
+/// Returns the host of a `url` claim or the domain of a `domain` claim, converted to punycode.
+fn domain(value: &str) -> Option<String> {
+    let url = if value.contains("://") {
+        Url::parse(value).ok()?
+    } else {
+        Url::parse(&format!("https://{value}")).ok()?
+    };
+    url.host_str().map(str::to_owned)
+}
+
+/// Returns `true` if the host is the claimed domain or one of its subdomains.
+pub(crate) fn covers(domain: &str, host: &str) -> bool {
+    let domain = domain.strip_prefix("www.").unwrap_or(domain);
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::test_util::credential;
+
+    #[test]
+    fn test_claim_conflicts() {
+        let holder = "did:example:shop";
+        let self_issued = credential(
+            "did:example:shop",
+            &[],
+            serde_json::json!({
+                "id": holder,
+                "legalName": "Other Shop Ltd",
+                "vatID": "de 123 456 789",
+                "address": { "addressCountry": "DE" }
+            }),
+        );
+        let registry = credential(
+            "did:example:registry",
+            &[],
+            serde_json::json!({
+                "id": holder,
+                "legalName": "Shop GmbH",
+                "vatID": "DE123456789",
+                "address": { "addressCountry": "de" }
+            }),
+        );
+        let credentials = [(0, holder, &self_issued), (1, holder, &registry)];
+
+        // the third-party credential is the reference
+        let inconsistencies = ConsistencyConfig::default()
+            .inconsistencies(credentials.into_iter(), Some("https://shop.example"));
+        assert_eq!(inconsistencies.len(), 1);
+        assert_eq!(inconsistencies[0].credential, 0);
+        assert_eq!(inconsistencies[0].code, "consistency.legal_name_conflict");
+        assert_eq!(inconsistencies[0].action, ConsistencyAction::Warn);
+
+        let config = ConsistencyConfig {
+            claim_conflicts: ConsistencyAction::Ignore,
+            ..ConsistencyConfig::default()
+        };
+        assert!(config
+            .inconsistencies(credentials.into_iter(), None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_origin_mismatch() {
+        let holder = "did:example:shop";
+        let matching = credential(
+            "did:example:registry",
+            &[],
+            serde_json::json!({ "id": holder, "url": "https://www.shop.example/" }),
+        );
+        let other = credential(
+            "did:example:directory",
+            &[],
+            serde_json::json!({ "id": holder, "domain": ["shop.test", "other.example"] }),
+        );
+        let config = ConsistencyConfig {
+            origin_mismatch: ConsistencyAction::Fail,
+            ..ConsistencyConfig::default()
+        };
+        let credentials = [(0, holder, &matching), (1, holder, &other)];
+
+        let inconsistencies = config.inconsistencies(
+            credentials.into_iter(),
+            Some("https://checkout.shop.example"),
+        );
+        assert_eq!(inconsistencies.len(), 1);
+        assert_eq!(inconsistencies[0].credential, 1);
+        assert_eq!(inconsistencies[0].code, "consistency.origin_mismatch");
+        assert_eq!(inconsistencies[0].action, ConsistencyAction::Fail);
+
+        // DIDs that are verified directly have no origin
+        assert!(config
+            .inconsistencies(credentials.into_iter(), None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize_name(" Shop  GmbH. "), normalize_name("shop gmbh"));
+        assert_ne!(normalize_name("Shop GmbH"), normalize_name("Other GmbH"));
+        assert_eq!(
+            normalize_identifier("DE 123.456.789"),
+            normalize_identifier("de123456789")
+        );
+    }
+
+    #[test]
+    fn test_covers() {
+        let covers = |claim: &str, host: &str| covers(&domain(claim).unwrap(), host);
+        assert!(covers("example.com", "example.com"));
+        assert!(covers("https://www.example.com/about", "example.com"));
+        assert!(covers("example.com", "shop.example.com"));
+        assert!(covers("bücher.example", "xn--bcher-kva.example"));
+        assert!(!covers("example.com", "example.org"));
+        assert!(!covers("example.com", "notexample.com"));
+    }
+
+    #[test]
+    fn test_parse_action() {
+        assert_eq!("fail".parse(), Ok(ConsistencyAction::Fail));
+        assert!("block".parse::<ConsistencyAction>().is_err());
+    }
+}
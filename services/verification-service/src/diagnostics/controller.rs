@@ -0,0 +1,42 @@
+use axum::extract::State;
+use url::Url;
+
+use super::service;
+use crate::AppState;
+use verification_service::dto::{
+    DiagnosisResponse, DiagnosisResponseDto, ProblemDetails, VerificationRequest,
+};
+
+/// Diagnose the deployment of a DID or URL
+///
+/// Runs every discovery step of the verification and reports all findings instead of stopping at the first failure.
+/// Checks include HTTP status, content type, CORS headers, redirects, JSON validity, the origin of the domain linkage
+/// credential, DID document service types and the expiry of credentials. Every finding comes with a severity and, if
+/// possible, a suggestion how to fix the issue.
+#[utoipa::path(
+    get,
+    path = "/v1/diagnose",
+    operation_id = "getDiagnosis",
+    params(("q" = String, Query, description = "Domain name, HTTPS URL or DID to diagnose.")),
+    responses(
+        (status = 200, description = "Diagnosis result.", body = DiagnosisResponseDto),
+        (status = 400, description = "Invalid query.", body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn diagnose_domain(
+    State(state): State<AppState>,
+    params: VerificationRequest,
+) -> DiagnosisResponse {
+    // safe to unwrap, URL has been parsed during DTO validation already
+    let url = Url::parse(&params.q).unwrap();
+
+    DiagnosisResponse::OK(
+        service::diagnose(
+            &url,
+            &state.fetch_config,
+            &state.resolver,
+            &state.checks.warnings,
+        )
+        .await,
+    )
+}
@@ -0,0 +1,10 @@
+use axum::{routing::get, Router};
+
+use crate::AppState;
+
+pub(crate) mod controller;
+pub(crate) mod service;
+
+pub fn create_router() -> Router<AppState> {
+    Router::new().route("/diagnose", get(controller::diagnose_domain))
+}
@@ -0,0 +1,1217 @@
+use reqwest::{
+    header::{HeaderMap, ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE, LOCATION, ORIGIN},
+    redirect::Policy,
+    Client, StatusCode,
+};
+use serde_json::Value;
+use ssi::claims::chrono::{DateTime, Utc};
+use ssi::dids::DIDResolver as _;
+use ssi::dids::{
+    document::{service::Endpoint, Service},
+    DIDBuf, Document,
+};
+use url::Url;
+
+use crate::verifications::service::{config_to_dids, url_to_didweb, url_to_well_known_config_uri};
+use verification_service::dto::{DiagnosisResponseDto, Finding};
+use verification_service::fetch::FetchConfig;
+use verification_service::presentation::parse_presentations;
+use verification_service::resolver::{didweb_to_url, DidResolver};
+use verification_service::warnings::WarningConfig;
+use verification_service::{
+    verify_did_configuration_vc_with, verify_encoded_vp_with, WellKnownDidConfig,
+};
+
+const LINKED_VP_TYPE: &str = "LinkedVerifiablePresentation";
+const DOMAIN_LINKAGE_TYPE: &str = "DomainLinkageCredential";
+const DID_CONFIGURATION_CONTEXT: &str =
+    "https://identity.foundation/.well-known/did-configuration/v1";
+/// Origin that is sent with every request to test the CORS configuration of the resources.
+const DIAGNOSIS_ORIGIN: &str = "https://check.identinet.io";
+
+/// HTTP client of the diagnosis. Redirects aren't followed automatically, so they can be reported.
+struct DiagnosisClient<'a> {
+    client: Client,
+    config: &'a FetchConfig,
+    /// Resolves the DIDs of issuers and holders with the limits of outbound requests.
+    resolver: &'a DidResolver,
+    /// Expiry window of the verification, so both report credentials that expire soon alike.
+    warnings: &'a WarningConfig,
+}
+
+/// Resource downloaded during the diagnosis, including the redirects that were followed.
+struct FetchedResource {
+    url: Url,
+    redirects: Vec<Url>,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: String,
+}
+
+/// Runs every discovery step for the given URL or DID and collects findings with remediation hints.
+///
+/// In contrast to [`crate::verifications::service::verify_by_url`] the diagnosis doesn't stop at the first failure but
+/// reports as many issues as possible. DIDs are resolved with the given resolver, so did:web documents of issuers and
+/// holders are downloaded with the same limits as during the verification.
+pub async fn diagnose(
+    url: &Url,
+    config: &FetchConfig,
+    resolver: &DidResolver,
+    warnings: &WarningConfig,
+) -> DiagnosisResponseDto {
+    let client = match config.client_builder().redirect(Policy::none()).build() {
+        Ok(client) => DiagnosisClient {
+            client,
+            config,
+            resolver,
+            warnings,
+        },
+        Err(e) => {
+            return DiagnosisResponseDto::from_findings(vec![Finding::error(
+                "diagnosis.client",
+                url.as_str(),
+                format!("HTTP client could not be initialized: {e}"),
+            )])
+        }
+    };
+    let mut findings = Vec::new();
+
+    let dids = match url.scheme() {
+        "did" => DIDBuf::from_string(url.to_string())
+            .map(|did| vec![did])
+            .unwrap_or_default(),
+        _ => diagnose_well_known(&client, url, &mut findings).await,
+    };
+
+    for did in &dids {
+        diagnose_did(&client, did, &mut findings).await;
+    }
+
+    DiagnosisResponseDto::from_findings(findings)
+}
+
+/// Checks the well-known DID configuration of the given URL and returns the DIDs that should be checked next.
+async fn diagnose_well_known(
+    client: &DiagnosisClient<'_>,
+    url: &Url,
+    findings: &mut Vec<Finding>,
+) -> Vec<DIDBuf> {
+    let Ok(well_known_uri) = url_to_well_known_config_uri(url) else {
+        return did_web_fallback(url, findings);
+    };
+    let resource = well_known_uri.as_str();
+
+    let fetched = match fetch(client, &well_known_uri).await {
+        Ok(fetched) => fetched,
+        Err(e) => {
+            findings.push(
+                Finding::warning(
+                    "well_known.unreachable",
+                    resource,
+                    format!("DID configuration could not be downloaded: {e}"),
+                )
+                .with_fix(
+                    "Make sure the web server is reachable via HTTPS with a valid TLS certificate."
+                        .to_owned(),
+                ),
+            );
+            return did_web_fallback(url, findings);
+        }
+    };
+
+    check_redirects(&fetched, "well_known", findings);
+    if !check_status(&fetched, "well_known", findings) {
+        return did_web_fallback(url, findings);
+    }
+    check_content_type(&fetched, "well_known", &["application/json"], findings);
+    check_cors(&fetched, "well_known", true, findings);
+
+    let Some(json) = check_json(&fetched, "well_known", findings) else {
+        return did_web_fallback(url, findings);
+    };
+
+    let errors_before = count_errors(findings);
+    check_did_configuration(&json, url, resource, client.warnings, findings);
+    if count_errors(findings) == errors_before {
+        // Only verify the proof when the structure is sound, otherwise the same issue would be reported twice
+        check_did_configuration_proof(client, &fetched.body, url, resource, findings).await;
+    }
+
+    match serde_json::from_str::<WellKnownDidConfig>(&fetched.body) {
+        Ok(config) => match config_to_dids(&config) {
+            dids if dids.is_empty() => did_web_fallback(url, findings),
+            dids => {
+                for did in &dids {
+                    findings.push(Finding::info(
+                        "discovery.did_configuration",
+                        resource,
+                        format!("Discovered DID '{}' via DID configuration.", did.as_str()),
+                    ));
+                }
+                dids
+            }
+        },
+        Err(_) => did_web_fallback(url, findings),
+    }
+}
+
+/// Derives the did:web DID from the given URL, like the verification does if no DID configuration is available.
+fn did_web_fallback(url: &Url, findings: &mut Vec<Finding>) -> Vec<DIDBuf> {
+    match url_to_didweb(url) {
+        Ok(dids) => {
+            for did in &dids {
+                findings.push(Finding::info(
+                    "discovery.did_web_fallback",
+                    url.as_str(),
+                    format!(
+                        "No usable DID configuration found, falling back to '{}'.",
+                        did.as_str()
+                    ),
+                ));
+            }
+            dids
+        }
+        Err(e) => {
+            findings.push(
+                Finding::error("discovery.failed", url.as_str(), format!("No DID could be discovered: {e}")).with_fix(
+                    "Publish a DID configuration at /.well-known/did-configuration.json or a did:web document at \
+                     /.well-known/did.json."
+                        .to_owned(),
+                ),
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Checks the structure of the DID configuration according to
+/// <https://identity.foundation/.well-known/resources/did-configuration/#did-configuration-resource>.
+fn check_did_configuration(
+    json: &Value,
+    url: &Url,
+    resource: &str,
+    warnings: &WarningConfig,
+    findings: &mut Vec<Finding>,
+) {
+    let has_context = match json.get("@context") {
+        Some(Value::String(context)) => context == DID_CONFIGURATION_CONTEXT,
+        Some(Value::Array(contexts)) => contexts
+            .iter()
+            .any(|context| context == DID_CONFIGURATION_CONTEXT),
+        _ => false,
+    };
+    if !has_context {
+        findings.push(
+            Finding::warning(
+                "did_configuration.context",
+                resource,
+                "DID configuration doesn't declare the DID configuration context.".to_owned(),
+            )
+            .with_fix(format!(
+                "Set \"@context\" to \"{DID_CONFIGURATION_CONTEXT}\"."
+            )),
+        );
+    }
+
+    let linked_dids = match json.get("linked_dids") {
+        Some(Value::Array(linked_dids)) if !linked_dids.is_empty() => linked_dids,
+        _ => {
+            findings.push(
+                Finding::error(
+                    "did_configuration.linked_dids",
+                    resource,
+                    "Property 'linked_dids' is missing or empty.".to_owned(),
+                )
+                .with_fix(
+                    "Add at least one domain linkage credential to the 'linked_dids' array."
+                        .to_owned(),
+                ),
+            );
+            return;
+        }
+    };
+
+    for linked_did in linked_dids {
+        match linked_did {
+            Value::Object(_) => {
+                check_domain_linkage_credential(linked_did, url, resource, warnings, findings);
+            }
+            Value::String(_) => findings.push(
+                Finding::warning(
+                    "did_configuration.jwt",
+                    resource,
+                    "JWT domain linkage credentials are not supported by the verification service.".to_owned(),
+                )
+                .with_fix("Add the domain linkage credential in JSON-LD format with a Data Integrity proof.".to_owned()),
+            ),
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::Array(_) => findings.push(Finding::error(
+                "did_configuration.linked_dids",
+                resource,
+                "Entries of 'linked_dids' must be credentials.".to_owned(),
+            )),
+        }
+    }
+}
+
+/// Checks a single domain linkage credential.
+fn check_domain_linkage_credential(
+    credential: &Value,
+    url: &Url,
+    resource: &str,
+    warnings: &WarningConfig,
+    findings: &mut Vec<Finding>,
+) {
+    if !contains_type(credential.get("type"), DOMAIN_LINKAGE_TYPE) {
+        findings.push(
+            Finding::error(
+                "did_configuration.credential_type",
+                resource,
+                format!("Credential is not of type '{DOMAIN_LINKAGE_TYPE}'."),
+            )
+            .with_fix(format!(
+                "Add \"{DOMAIN_LINKAGE_TYPE}\" to the credential's \"type\" property."
+            )),
+        );
+    }
+
+    let issuer = match credential.get("issuer") {
+        Some(Value::String(issuer)) => Some(issuer.as_str()),
+        Some(Value::Object(issuer)) => issuer.get("id").and_then(Value::as_str),
+        _ => None,
+    };
+    let subject = credential.get("credentialSubject");
+    let subject_id = subject.and_then(|s| s.get("id")).and_then(Value::as_str);
+    match subject_id {
+        Some(id) if id.starts_with("did:") => {
+            if issuer != Some(id) {
+                findings.push(
+                    Finding::error(
+                        "did_configuration.subject_issuer",
+                        resource,
+                        format!("credentialSubject.id '{id}' must be equal to the issuer."),
+                    )
+                    .with_fix(format!(
+                        "Issue the domain linkage credential with the DID '{id}'."
+                    )),
+                );
+            }
+        }
+        _ => findings.push(
+            Finding::error(
+                "did_configuration.subject_id",
+                resource,
+                "credentialSubject.id must be a DID.".to_owned(),
+            )
+            .with_fix(
+                "Set credentialSubject.id to the DID that shall be linked to the domain."
+                    .to_owned(),
+            ),
+        ),
+    }
+
+    match subject
+        .and_then(|s| s.get("origin"))
+        .and_then(Value::as_str)
+    {
+        Some(origin) => check_origin_format(origin, url, resource, findings),
+        None => findings.push(
+            Finding::error(
+                "did_configuration.origin",
+                resource,
+                "credentialSubject.origin is missing.".to_owned(),
+            )
+            .with_fix(format!(
+                "Set credentialSubject.origin to \"{}\".",
+                url.origin().ascii_serialization()
+            )),
+        ),
+    }
+
+    check_expiry(
+        credential,
+        "did_configuration",
+        resource,
+        Utc::now(),
+        warnings,
+        findings,
+    );
+}
+
+/// The origin must be serialized as an origin, i.e. without path or trailing slash, and it must match the requested
+/// origin.
+fn check_origin_format(origin: &str, url: &Url, resource: &str, findings: &mut Vec<Finding>) {
+    let expected = url.origin().ascii_serialization();
+    let fix = format!("Set credentialSubject.origin to \"{expected}\".");
+    match Url::parse(origin) {
+        Ok(parsed) if parsed.origin().ascii_serialization() == origin => {
+            if origin != expected {
+                findings.push(
+                    Finding::error(
+                        "did_configuration.origin_mismatch",
+                        resource,
+                        format!("credentialSubject.origin '{origin}' doesn't match the requested origin '{expected}'."),
+                    )
+                    .with_fix(fix),
+                );
+            }
+        }
+        Ok(_) => findings.push(
+            Finding::error(
+                "did_configuration.origin_format",
+                resource,
+                format!(
+                    "credentialSubject.origin '{origin}' must only consist of scheme, host and port, without path, \
+                     query or trailing slash."
+                ),
+            )
+            .with_fix(fix),
+        ),
+        Err(e) => findings.push(
+            Finding::error(
+                "did_configuration.origin_format",
+                resource,
+                format!("credentialSubject.origin '{origin}' is not a valid origin: {e}"),
+            )
+            .with_fix(fix),
+        ),
+    }
+}
+
+/// Reports expired credentials, credentials that expire within the expiry window and credentials without expiration
+/// date.
+fn check_expiry(
+    credential: &Value,
+    prefix: &str,
+    resource: &str,
+    now: DateTime<Utc>,
+    warnings: &WarningConfig,
+    findings: &mut Vec<Finding>,
+) {
+    let expiration = credential
+        .get("expirationDate")
+        .or_else(|| credential.get("validUntil"))
+        .and_then(Value::as_str);
+    let Some(expiration) = expiration else {
+        if prefix == "did_configuration" {
+            findings.push(
+                Finding::warning(
+                    &format!("{prefix}.expiration_missing"),
+                    resource,
+                    "Domain linkage credential has no expiration date.".to_owned(),
+                )
+                .with_fix(
+                    "Reissue the domain linkage credential with an expirationDate.".to_owned(),
+                ),
+            );
+        }
+        return;
+    };
+    match DateTime::parse_from_rfc3339(expiration) {
+        Ok(expiration) if expiration < now => findings.push(
+            Finding::error(
+                &format!("{prefix}.expired"),
+                resource,
+                format!("Credential expired at {}.", expiration.to_rfc3339()),
+            )
+            .with_fix("Reissue the credential with an expiration date in the future.".to_owned()),
+        ),
+        Ok(expiration) if warnings.expires_soon(expiration.to_utc(), now) => findings.push(
+            Finding::warning(
+                &format!("{prefix}.expires_soon"),
+                resource,
+                format!("Credential expires at {}.", expiration.to_rfc3339()),
+            )
+            .with_fix("Reissue the credential before it expires.".to_owned()),
+        ),
+        Ok(_) => {}
+        Err(e) => findings.push(
+            Finding::error(
+                &format!("{prefix}.expiration_format"),
+                resource,
+                format!("Expiration date '{expiration}' is not a valid date: {e}"),
+            )
+            .with_fix("Use an XML Schema dateTime, e.g. 2030-01-01T00:00:00Z.".to_owned()),
+        ),
+    }
+}
+
+/// Verifies the proof of the domain linkage credential. The DID of the issuer is resolved with the shared resolver.
+async fn check_did_configuration_proof(
+    client: &DiagnosisClient<'_>,
+    body: &String,
+    url: &Url,
+    resource: &str,
+    findings: &mut Vec<Finding>,
+) {
+    if let Err(e) = verify_did_configuration_vc_with(body, url, client.resolver).await {
+        let payload = e.payload();
+        findings.push(
+            Finding::error(
+                "did_configuration.verification",
+                resource,
+                format!("{} {}", payload.message, payload.details),
+            )
+            .with_fix(e.fix(None)),
+        );
+    }
+}
+
+/// Resolves the DID document and checks the Linked Verifiable Presentation services.
+async fn diagnose_did(client: &DiagnosisClient<'_>, did: &DIDBuf, findings: &mut Vec<Finding>) {
+    let resource = did.as_str();
+    let document = match didweb_to_url(did) {
+        Some(did_url) => fetch_did_web_document(client, &did_url, findings).await,
+        None => match client.resolver.resolve(did.as_did()).await {
+            Ok(output) => Some(output.document.into_document()),
+            Err(e) => {
+                findings.push(Finding::error(
+                    "did_document.resolution",
+                    resource,
+                    format!("DID could not be resolved: {e}"),
+                ));
+                None
+            }
+        },
+    };
+    let Some(document) = document else {
+        return;
+    };
+
+    if document.id.as_str() != resource {
+        findings.push(
+            Finding::error(
+                "did_document.id",
+                resource,
+                format!(
+                    "DID document id '{}' doesn't match the DID.",
+                    document.id.as_str()
+                ),
+            )
+            .with_fix(format!(
+                "Set the \"id\" of the DID document to \"{resource}\"."
+            )),
+        );
+    }
+
+    for endpoint in check_services(&document.service, resource, findings) {
+        diagnose_linked_presentation(client, &endpoint, did, findings).await;
+    }
+}
+
+/// Downloads the did:web document and checks the HTTP response.
+async fn fetch_did_web_document(
+    client: &DiagnosisClient<'_>,
+    url: &Url,
+    findings: &mut Vec<Finding>,
+) -> Option<Document> {
+    let fetched = match fetch(client, url).await {
+        Ok(fetched) => fetched,
+        Err(e) => {
+            findings.push(
+                Finding::error(
+                    "did_document.unreachable",
+                    url.as_str(),
+                    format!("DID document could not be downloaded: {e}"),
+                )
+                .with_fix(
+                    "Make sure the web server is reachable via HTTPS with a valid TLS certificate."
+                        .to_owned(),
+                ),
+            );
+            return None;
+        }
+    };
+    check_redirects(&fetched, "did_document", findings);
+    if !check_status(&fetched, "did_document", findings) {
+        return None;
+    }
+    check_content_type(
+        &fetched,
+        "did_document",
+        &["application/did+json", "application/json"],
+        findings,
+    );
+    check_json(&fetched, "did_document", findings)?;
+    match serde_json::from_str::<Document>(&fetched.body) {
+        Ok(document) => Some(document),
+        Err(e) => {
+            findings.push(
+                Finding::error(
+                    "did_document.invalid",
+                    fetched.url.as_str(),
+                    format!("Document is not a valid DID document: {e}"),
+                )
+                .with_fix(
+                    "Validate the DID document against https://www.w3.org/TR/did-core/.".to_owned(),
+                ),
+            );
+            None
+        }
+    }
+}
+
+/// Checks the services of a DID document and returns the endpoints of all Linked Verifiable Presentation services.
+fn check_services(services: &[Service], resource: &str, findings: &mut Vec<Finding>) -> Vec<Url> {
+    let mut endpoints = Vec::new();
+    for service in services {
+        let is_linked_vp = service.type_.contains(&LINKED_VP_TYPE.to_owned());
+        if !is_linked_vp {
+            // Catch common misspellings that cause the service to be ignored silently
+            if let Some(type_) = (&service.type_)
+                .into_iter()
+                .find(|type_| is_misspelled_linked_vp_type(type_))
+            {
+                findings.push(
+                    Finding::error(
+                        "did_document.service_type",
+                        resource,
+                        format!(
+                            "Service '{}' has type '{type_}' which is not recognized.",
+                            service.id
+                        ),
+                    )
+                    .with_fix(format!("Change the service type to \"{LINKED_VP_TYPE}\".")),
+                );
+            }
+            continue;
+        }
+
+        for endpoint in service.service_endpoint.iter().flat_map(|e| e.into_iter()) {
+            match endpoint {
+                Endpoint::Uri(uri) => match Url::parse(uri.as_str()) {
+                    Ok(url) if url.scheme() == "https" => endpoints.push(url),
+                    Ok(url) => findings.push(
+                        Finding::error(
+                            "did_document.service_endpoint",
+                            resource,
+                            format!("Service endpoint '{url}' doesn't use HTTPS."),
+                        )
+                        .with_fix("Serve the Linked Verifiable Presentation via HTTPS.".to_owned()),
+                    ),
+                    Err(e) => findings.push(Finding::error(
+                        "did_document.service_endpoint",
+                        resource,
+                        format!("Service endpoint '{uri}' is not a valid URL: {e}"),
+                    )),
+                },
+                Endpoint::Map(_) => findings.push(
+                    Finding::warning(
+                        "did_document.service_endpoint",
+                        resource,
+                        format!(
+                            "Service '{}' uses a map endpoint which is not supported.",
+                            service.id
+                        ),
+                    )
+                    .with_fix("Use a URL as service endpoint.".to_owned()),
+                ),
+            }
+        }
+    }
+
+    if endpoints.is_empty() {
+        findings.push(
+            Finding::warning(
+                "did_document.linked_vp_missing",
+                resource,
+                "DID document doesn't reference any Linked Verifiable Presentation.".to_owned(),
+            )
+            .with_fix(format!(
+                "Add a service of type \"{LINKED_VP_TYPE}\" whose serviceEndpoint points to a verifiable \
+                 presentation, see https://identity.foundation/linked-vp/."
+            )),
+        );
+    }
+    endpoints
+}
+
+fn is_misspelled_linked_vp_type(type_: &str) -> bool {
+    let normalized = type_.to_ascii_lowercase().replace(['-', '_', ' '], "");
+    normalized.starts_with("linkedverifiablepresentation") || normalized.starts_with("linkedvp")
+}
+
+/// Downloads and verifies a Linked Verifiable Presentation.
+async fn diagnose_linked_presentation(
+    client: &DiagnosisClient<'_>,
+    endpoint: &Url,
+    did: &DIDBuf,
+    findings: &mut Vec<Finding>,
+) {
+    let resource = endpoint.as_str();
+    let fetched = match fetch(client, endpoint).await {
+        Ok(fetched) => fetched,
+        Err(e) => {
+            findings.push(
+                Finding::error(
+                    "linked_vp.unreachable",
+                    resource,
+                    format!("Linked Verifiable Presentation could not be downloaded: {e}"),
+                )
+                .with_fix("Make sure the service endpoint is reachable via HTTPS.".to_owned()),
+            );
+            return;
+        }
+    };
+    check_redirects(&fetched, "linked_vp", findings);
+    if !check_status(&fetched, "linked_vp", findings) {
+        return;
+    }
+    check_content_type(
+        &fetched,
+        "linked_vp",
+        &[
+            "application/json",
+            "application/ld+json",
+            "application/jwt",
+            "application/vp+jwt",
+        ],
+        findings,
+    );
+    check_cors(&fetched, "linked_vp", false, findings);
+    let content_type = fetched
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let presentations = match parse_presentations(&fetched.body, content_type) {
+        Ok(presentations) => presentations,
+        Err(e) => {
+            findings.push(
+                Finding::error(
+                    "linked_vp.invalid",
+                    resource,
+                    format!("Document is not a valid verifiable presentation: {e}"),
+                )
+                .with_fix(
+                    "Serve a presentation or an array of presentations as JSON or JWT, see \
+                     https://identity.foundation/linked-vp/."
+                        .to_owned(),
+                ),
+            );
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    let mut results = Vec::new();
+    for presentation in &presentations {
+        // credentials in JWT form have been decoded
+        for credential in &presentation.presentation().verifiable_credentials {
+            if let Ok(credential) = serde_json::to_value(credential) {
+                check_expiry(
+                    &credential,
+                    "linked_vp",
+                    resource,
+                    now,
+                    client.warnings,
+                    findings,
+                );
+            }
+        }
+        match verify_encoded_vp_with(presentation, did, true, client.resolver).await {
+            Ok(presentation_results) => results.extend(presentation_results),
+            Err(e) => results.push(e),
+        }
+    }
+    for result in results.iter().filter(|result| !result.payload().verified) {
+        let payload = result.payload();
+        findings.push(
+            Finding::error(
+                "linked_vp.verification",
+                resource,
+                format!("{} {}", payload.message, payload.details)
+                    .trim_end()
+                    .to_owned(),
+            )
+            .with_fix(result.fix(Some(did.as_str()))),
+        );
+    }
+}
+
+/// Downloads the given URL without following redirects automatically, so they can be reported.
+async fn fetch(client: &DiagnosisClient<'_>, url: &Url) -> Result<FetchedResource, String> {
+    let mut current = url.clone();
+    let mut redirects = Vec::new();
+    loop {
+        client
+            .config
+            .check_url(&current)
+            .map_err(|e| e.to_string())?;
+        let response = client
+            .client
+            .get(current.clone())
+            .header(ORIGIN, DIAGNOSIS_ORIGIN)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_redirection() {
+            if redirects.len() >= client.config.max_redirects {
+                return Err(format!(
+                    "more than {} redirects",
+                    client.config.max_redirects
+                ));
+            }
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or_else(|| "redirect without Location header".to_owned())?;
+            let next = current.join(location).map_err(|e| e.to_string())?;
+            redirects.push(current);
+            current = next;
+            continue;
+        }
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = client
+            .config
+            .read_body(response)
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok(FetchedResource {
+            url: current,
+            redirects,
+            status,
+            headers,
+            body,
+        });
+    }
+}
+
+fn check_redirects(fetched: &FetchedResource, prefix: &str, findings: &mut Vec<Finding>) {
+    let Some(first) = fetched.redirects.first() else {
+        return;
+    };
+    if fetched.url.scheme() != "https" {
+        findings.push(
+            Finding::error(
+                &format!("{prefix}.redirect"),
+                first.as_str(),
+                format!("Resource redirects to the insecure URL '{}'.", fetched.url),
+            )
+            .with_fix("Serve the resource via HTTPS without redirects.".to_owned()),
+        );
+    } else if fetched.url.origin() == first.origin() {
+        findings.push(
+            Finding::info(
+                &format!("{prefix}.redirect"),
+                first.as_str(),
+                format!(
+                    "Resource is served via redirect from '{first}' to '{}'.",
+                    fetched.url
+                ),
+            )
+            .with_fix(format!("Serve the resource directly at '{first}'.")),
+        );
+    } else {
+        findings.push(
+            Finding::warning(
+                &format!("{prefix}.redirect"),
+                first.as_str(),
+                format!("Resource redirects to another origin '{}'.", fetched.url),
+            )
+            .with_fix(format!(
+                "Serve the resource directly at '{first}'; some clients don't follow redirects."
+            )),
+        );
+    }
+}
+
+/// Returns `true` if the status code indicates success.
+fn check_status(fetched: &FetchedResource, prefix: &str, findings: &mut Vec<Finding>) -> bool {
+    if fetched.status.is_success() {
+        return true;
+    }
+    let fix = if fetched.status == StatusCode::NOT_FOUND {
+        format!("Publish the resource at '{}'.", fetched.url)
+    } else {
+        "Check the web server configuration, the resource must be returned with status 200."
+            .to_owned()
+    };
+    findings.push(
+        Finding::error(
+            &format!("{prefix}.status"),
+            fetched.url.as_str(),
+            format!("Server responded with HTTP status {}.", fetched.status),
+        )
+        .with_fix(fix),
+    );
+    false
+}
+
+fn check_content_type(
+    fetched: &FetchedResource,
+    prefix: &str,
+    expected: &[&str],
+    findings: &mut Vec<Finding>,
+) {
+    let media_type = fetched
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase());
+    let matches = media_type
+        .as_deref()
+        .is_some_and(|media_type| expected.contains(&media_type));
+    if !matches {
+        findings.push(
+            Finding::warning(
+                &format!("{prefix}.content_type"),
+                fetched.url.as_str(),
+                format!(
+                    "Resource is served with content type '{}'.",
+                    media_type.as_deref().unwrap_or("none")
+                ),
+            )
+            .with_fix(format!(
+                "Serve the resource with header 'Content-Type: {}'.",
+                expected[0]
+            )),
+        );
+    }
+}
+
+/// Checks the `Access-Control-Allow-Origin` header. When `required` is set, a missing header is an error, otherwise
+/// it's a warning.
+fn check_cors(
+    fetched: &FetchedResource,
+    prefix: &str,
+    required: bool,
+    findings: &mut Vec<Finding>,
+) {
+    let allowed = fetched
+        .headers
+        .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim() == "*" || value.trim() == DIAGNOSIS_ORIGIN);
+    if !allowed {
+        let message =
+            "Resource can't be accessed from other origins, the CORS header is missing.".to_owned();
+        let code = format!("{prefix}.cors");
+        let finding = if required {
+            Finding::error(&code, fetched.url.as_str(), message)
+        } else {
+            Finding::warning(&code, fetched.url.as_str(), message)
+        };
+        findings.push(finding.with_fix(
+            "Serve the resource with header 'Access-Control-Allow-Origin: *'.".to_owned(),
+        ));
+    }
+}
+
+fn check_json(
+    fetched: &FetchedResource,
+    prefix: &str,
+    findings: &mut Vec<Finding>,
+) -> Option<Value> {
+    match serde_json::from_str::<Value>(&fetched.body) {
+        Ok(json) => Some(json),
+        Err(e) => {
+            findings.push(
+                Finding::error(
+                    &format!("{prefix}.json"),
+                    fetched.url.as_str(),
+                    format!("Resource is not valid JSON: {e}"),
+                )
+                .with_fix("Validate the document with a JSON linter.".to_owned()),
+            );
+            None
+        }
+    }
+}
+
+fn contains_type(types: Option<&Value>, expected: &str) -> bool {
+    match types {
+        Some(Value::String(type_)) => type_ == expected,
+        Some(Value::Array(types)) => types.iter().any(|type_| type_ == expected),
+        _ => false,
+    }
+}
+
+fn count_errors(findings: &[Finding]) -> usize {
+    findings
+        .iter()
+        .filter(|finding| finding.severity == verification_service::dto::Severity::Error)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+    use reqwest::header::HeaderValue;
+    use std::collections::HashMap;
+    use verification_service::dto::Severity;
+    use verification_service::fetch::Fetcher;
+
+    fn fetched(headers: &[(reqwest::header::HeaderName, &'static str)]) -> FetchedResource {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.insert(name.clone(), HeaderValue::from_static(value));
+        }
+        FetchedResource {
+            url: Url::parse("https://example.com/.well-known/did-configuration.json").unwrap(),
+            redirects: Vec::new(),
+            status: StatusCode::OK,
+            headers: header_map,
+            body: String::new(),
+        }
+    }
+
+    fn codes(findings: &[Finding]) -> Vec<(&str, Severity)> {
+        findings
+            .iter()
+            .map(|finding| (finding.code.as_str(), finding.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_check_origin_format() {
+        let url = Url::parse("https://example.com/shop").unwrap();
+
+        let mut findings = Vec::new();
+        check_origin_format("https://example.com", &url, "r", &mut findings);
+        assert!(findings.is_empty());
+
+        check_origin_format("https://example.com/", &url, "r", &mut findings);
+        check_origin_format("example.com", &url, "r", &mut findings);
+        check_origin_format("https://other.example.com", &url, "r", &mut findings);
+        assert_eq!(
+            codes(&findings),
+            vec![
+                ("did_configuration.origin_format", Severity::Error),
+                ("did_configuration.origin_format", Severity::Error),
+                ("did_configuration.origin_mismatch", Severity::Error),
+            ]
+        );
+        assert_eq!(
+            findings[0].fix.as_deref(),
+            Some("Set credentialSubject.origin to \"https://example.com\".")
+        );
+    }
+
+    #[test]
+    fn test_check_content_type_and_cors() {
+        let mut findings = Vec::new();
+        let ok = fetched(&[
+            (CONTENT_TYPE, "application/json; charset=utf-8"),
+            (ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+        ]);
+        check_content_type(&ok, "well_known", &["application/json"], &mut findings);
+        check_cors(&ok, "well_known", true, &mut findings);
+        assert!(findings.is_empty());
+
+        let bad = fetched(&[(CONTENT_TYPE, "text/html")]);
+        check_content_type(&bad, "well_known", &["application/json"], &mut findings);
+        check_cors(&bad, "well_known", true, &mut findings);
+        check_cors(&bad, "linked_vp", false, &mut findings);
+        assert_eq!(
+            codes(&findings),
+            vec![
+                ("well_known.content_type", Severity::Warning),
+                ("well_known.cors", Severity::Error),
+                ("linked_vp.cors", Severity::Warning),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_redirects() {
+        let mut findings = Vec::new();
+        let mut redirected = fetched(&[]);
+        redirected.redirects.push(
+            Url::parse("https://www.example.com/.well-known/did-configuration.json").unwrap(),
+        );
+        check_redirects(&redirected, "well_known", &mut findings);
+        redirected.url =
+            Url::parse("http://example.com/.well-known/did-configuration.json").unwrap();
+        check_redirects(&redirected, "well_known", &mut findings);
+        assert_eq!(
+            codes(&findings),
+            vec![
+                ("well_known.redirect", Severity::Warning),
+                ("well_known.redirect", Severity::Error)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_expiry() {
+        let now = DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z")
+            .unwrap()
+            .to_utc();
+        let warnings = WarningConfig::default();
+        let mut findings = Vec::new();
+        check_expiry(
+            &serde_json::json!({"expirationDate": "2030-01-01T00:00:00Z"}),
+            "linked_vp",
+            "r",
+            now,
+            &warnings,
+            &mut findings,
+        );
+        check_expiry(
+            &serde_json::json!({}),
+            "linked_vp",
+            "r",
+            now,
+            &warnings,
+            &mut findings,
+        );
+        assert!(findings.is_empty());
+
+        check_expiry(
+            &serde_json::json!({"expirationDate": "2024-01-01T00:00:00Z"}),
+            "linked_vp",
+            "r",
+            now,
+            &warnings,
+            &mut findings,
+        );
+        check_expiry(
+            &serde_json::json!({"validUntil": "2025-01-10T00:00:00Z"}),
+            "linked_vp",
+            "r",
+            now,
+            &warnings,
+            &mut findings,
+        );
+        check_expiry(
+            &serde_json::json!({"expirationDate": "tomorrow"}),
+            "linked_vp",
+            "r",
+            now,
+            &warnings,
+            &mut findings,
+        );
+        check_expiry(
+            &serde_json::json!({}),
+            "did_configuration",
+            "r",
+            now,
+            &warnings,
+            &mut findings,
+        );
+        assert_eq!(
+            codes(&findings),
+            vec![
+                ("linked_vp.expired", Severity::Error),
+                ("linked_vp.expires_soon", Severity::Warning),
+                ("linked_vp.expiration_format", Severity::Error),
+                ("did_configuration.expiration_missing", Severity::Warning),
+            ]
+        );
+
+        // the expiry window of the verification applies
+        let credential = serde_json::json!({"expirationDate": "2025-02-15T00:00:00Z"});
+        let mut findings = Vec::new();
+        check_expiry(&credential, "linked_vp", "r", now, &warnings, &mut findings);
+        assert!(findings.is_empty());
+        let warnings =
+            WarningConfig::default().with_expiry_window(Duration::from_secs(60 * 24 * 60 * 60));
+        check_expiry(&credential, "linked_vp", "r", now, &warnings, &mut findings);
+        assert_eq!(
+            codes(&findings),
+            vec![("linked_vp.expires_soon", Severity::Warning)]
+        );
+    }
+
+    #[test]
+    fn test_check_did_configuration() {
+        let url = Url::parse("https://example.com").unwrap();
+        let json: Value = serde_json::from_str(include_str!(
+            "../../tests/did-configurations/did-config-holder.json"
+        ))
+        .unwrap();
+        let mut findings = Vec::new();
+        let warnings = WarningConfig::default();
+        check_did_configuration(&json, &url, "r", &warnings, &mut findings);
+        assert_eq!(count_errors(&findings), 0);
+
+        let mut findings = Vec::new();
+        check_did_configuration(
+            &serde_json::json!({"linked_dids": []}),
+            &url,
+            "r",
+            &warnings,
+            &mut findings,
+        );
+        assert_eq!(
+            codes(&findings),
+            vec![
+                ("did_configuration.context", Severity::Warning),
+                ("did_configuration.linked_dids", Severity::Error),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_did_configuration_proof_private_did_web() {
+        let config = FetchConfig::default();
+        let resolver = DidResolver::default().with_fetcher(Fetcher::new(config.clone()).unwrap());
+        let client = DiagnosisClient {
+            client: config.client_builder().build().unwrap(),
+            config: &config,
+            resolver: &resolver,
+            warnings: &WarningConfig::default(),
+        };
+        // the issuer's did:web document would be downloaded from the cloud metadata endpoint
+        let body = include_str!("../../tests/did-configurations/did-config-holder.json").replace(
+            include_str!("../../tests/dids/did-holder").trim(),
+            "did:web:169.254.169.254",
+        );
+        let url = Url::parse("https://example.com").unwrap();
+        let mut findings = Vec::new();
+        check_did_configuration_proof(&client, &body, &url, "r", &mut findings).await;
+        assert_eq!(
+            codes(&findings),
+            vec![("did_configuration.verification", Severity::Error)]
+        );
+        assert!(
+            findings[0].message.contains("Destination not allowed"),
+            "{}",
+            findings[0].message
+        );
+    }
+
+    #[tokio::test]
+    async fn test_did_configuration_proof_shared_fetcher() {
+        // the fetcher carries the proxy, root certificates and user agent of the service
+        let holder = include_str!("../../tests/dids/did-holder").trim();
+        let did_document = include_str!("../../tests/dids/did-doc-holder.json")
+            .replace(holder, "did:web:example.com");
+        let fetcher = Fetcher::from_documents(HashMap::from([(
+            Url::parse("https://example.com/.well-known/did.json").unwrap(),
+            did_document,
+        )]));
+        let config = FetchConfig::default();
+        let resolver = DidResolver::default().with_fetcher(fetcher);
+        let client = DiagnosisClient {
+            client: config.client_builder().build().unwrap(),
+            config: &config,
+            resolver: &resolver,
+            warnings: &WarningConfig::default(),
+        };
+        let body = include_str!("../../tests/did-configurations/did-config-holder.json")
+            .replace(holder, "did:web:example.com");
+        let url = Url::parse("https://example.com").unwrap();
+        let mut findings = Vec::new();
+        check_did_configuration_proof(&client, &body, &url, "r", &mut findings).await;
+        // the issuer has been resolved, only the signature doesn't match the altered credential
+        assert_eq!(findings.len(), 1);
+        assert!(
+            findings[0].message.contains("signature"),
+            "{}",
+            findings[0].message
+        );
+    }
+
+    #[test]
+    fn test_misspelled_service_type() {
+        assert!(is_misspelled_linked_vp_type(
+            "LinkedVerifiablePresentations"
+        ));
+        assert!(is_misspelled_linked_vp_type("linked-vp"));
+        assert!(!is_misspelled_linked_vp_type("LinkedDomains"));
+    }
+}
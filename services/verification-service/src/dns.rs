@@ -0,0 +1,518 @@
+use core::{fmt, future::Future, net::SocketAddr, pin::Pin, time::Duration};
+use std::{
+    fs,
+    hash::{BuildHasher as _, RandomState},
+    path::Path,
+};
+
+use hickory_proto::{
+    op::{Edns, Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{Name, RData, Record, RecordType},
+};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::{TcpStream, UdpSocket},
+    time::timeout,
+};
+
+use crate::concurrency;
+
+/// Maximum size of a UDP response that is announced via EDNS, see <https://www.dnsflagday.net/2020/>.
+const MAX_UDP_PAYLOAD: u16 = 1232;
+
+/// DNS error.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum DnsError {
+    /// The query failed or the response couldn't be read
+    #[error("Query failed: {0}")]
+    Query(String),
+
+    /// The server didn't respond in time
+    #[error("Query timed out: {0}")]
+    Timeout(String),
+
+    /// The resolver configuration is invalid, e.g. no name server is configured
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+}
+
+impl From<std::io::Error> for DnsError {
+    #[inline]
+    fn from(e: std::io::Error) -> Self {
+        Self::Query(e.to_string())
+    }
+}
+
+/// DNSSEC validation status of a lookup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DnssecStatus {
+    /// The records have been validated by the resolver.
+    Secure,
+    /// The records haven't been validated, e.g. because the zone isn't signed or the resolver doesn't validate.
+    Insecure,
+}
+
+/// TXT records of a name.
+#[derive(Clone, Debug)]
+pub struct TxtLookup {
+    /// Records, the character strings of a record are concatenated.
+    pub records: Vec<String>,
+    /// DNSSEC validation status of the records.
+    pub dnssec: DnssecStatus,
+}
+
+/// Future that is returned by [`DnsResolver`].
+pub type DnsFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, DnsError>> + Send + 'a>>;
+
+/// Resolver of DNS records that are used to discover DIDs.
+pub trait DnsResolver: fmt::Debug + Send + Sync {
+    /// Returns the TXT records of the fully qualified name. Names without records result in an empty lookup.
+    fn txt_lookup<'a>(&'a self, name: &'a str) -> DnsFuture<'a, TxtLookup>;
+}
+
+/// Stub resolver that sends queries to a recursive name server.
+///
+/// The name server is expected to validate DNSSEC signatures. Records are reported as [`DnssecStatus::Secure`] if the
+/// server sets the Authenticated Data flag, see <https://www.rfc-editor.org/rfc/rfc6840#section-5.8>. Thus, the flag is
+/// only trustworthy if the path to the name server is, e.g. a resolver on the same host or in the same cluster.
+/// Queries are sent via UDP and repeated via TCP if the response has been truncated.
+#[derive(Clone, Debug)]
+pub struct StubResolver {
+    server: SocketAddr,
+    timeout: Duration,
+}
+
+impl StubResolver {
+    /// Creates a resolver that queries the given name server.
+    #[inline]
+    #[must_use]
+    pub const fn new(server: SocketAddr) -> Self {
+        Self {
+            server,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Creates a resolver that queries the first name server of `/etc/resolv.conf`.
+    #[inline]
+    pub fn from_system_config() -> Result<Self, DnsError> {
+        Self::from_resolv_conf(Path::new("/etc/resolv.conf"))
+    }
+
+    /// Creates a resolver that queries the first name server of the given `resolv.conf` file.
+    #[inline]
+    pub fn from_resolv_conf(path: &Path) -> Result<Self, DnsError> {
+        let config = fs::read_to_string(path)
+            .map_err(|e| DnsError::Config(format!("{}: {e}", path.display())))?;
+        config
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("nameserver"))
+            .filter_map(|address| address.trim().parse().ok())
+            .map(|ip| Self::new(SocketAddr::new(ip, 53)))
+            .next()
+            .ok_or_else(|| {
+                DnsError::Config(format!("{}: no name server configured", path.display()))
+            })
+    }
+
+    /// Sets the timeout of a query, including the retry via TCP.
+    #[inline]
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends the query and returns the response.
+    async fn query(&self, name: &str, record_type: RecordType) -> Result<Message, DnsError> {
+        let mut name = Name::from_ascii(name).map_err(|e| DnsError::Query(e.to_string()))?;
+        // names in responses are always fully qualified
+        name.set_fqdn(true);
+        let mut request = Message::new();
+        let mut edns = Edns::new();
+        edns.set_max_payload(MAX_UDP_PAYLOAD);
+        request
+            // a random ID makes it harder to spoof responses
+            .set_id(RandomState::new().hash_one(&name) as u16)
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(true)
+            // request the validation status, see https://www.rfc-editor.org/rfc/rfc6840#section-5.7
+            .set_authentic_data(true)
+            .set_edns(edns)
+            .add_query(Query::query(name, record_type));
+        let request_bytes = request
+            .to_vec()
+            .map_err(|e| DnsError::Query(e.to_string()))?;
+
+        let _permit = concurrency::acquire_fetch().await;
+        let exchange = async {
+            let response = self.exchange_udp(&request, &request_bytes).await?;
+            if response.truncated() {
+                self.exchange_tcp(&request, &request_bytes).await
+            } else {
+                Ok(response)
+            }
+        };
+        timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| DnsError::Timeout(format!("{} didn't respond", self.server)))?
+    }
+
+    /// Sends the query via UDP. Responses that don't answer the request are ignored.
+    async fn exchange_udp(
+        &self,
+        request: &Message,
+        request_bytes: &[u8],
+    ) -> Result<Message, DnsError> {
+        let local: SocketAddr = if self.server.is_ipv6() {
+            "[::]:0".parse().expect("address is valid")
+        } else {
+            "0.0.0.0:0".parse().expect("address is valid")
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(self.server).await?;
+        socket.send(request_bytes).await?;
+        let mut buffer = vec![0; usize::from(MAX_UDP_PAYLOAD)];
+        loop {
+            let length = socket.recv(&mut buffer).await?;
+            if let Ok(response) = Message::from_vec(&buffer[..length]) {
+                if answers(request, &response) {
+                    return Ok(response);
+                }
+            }
+        }
+    }
+
+    /// Sends the query via TCP, see <https://www.rfc-editor.org/rfc/rfc1035#section-4.2.2>.
+    async fn exchange_tcp(
+        &self,
+        request: &Message,
+        request_bytes: &[u8],
+    ) -> Result<Message, DnsError> {
+        let mut stream = TcpStream::connect(self.server).await?;
+        let length =
+            u16::try_from(request_bytes.len()).map_err(|e| DnsError::Query(e.to_string()))?;
+        stream.write_all(&length.to_be_bytes()).await?;
+        stream.write_all(request_bytes).await?;
+        let length = stream.read_u16().await?;
+        let mut buffer = vec![0; usize::from(length)];
+        stream.read_exact(&mut buffer).await?;
+        let response = Message::from_vec(&buffer).map_err(|e| DnsError::Query(e.to_string()))?;
+        if answers(request, &response) {
+            Ok(response)
+        } else {
+            Err(DnsError::Query(
+                "response doesn't match the query".to_owned(),
+            ))
+        }
+    }
+}
+
+impl DnsResolver for StubResolver {
+    #[inline]
+    fn txt_lookup<'a>(&'a self, name: &'a str) -> DnsFuture<'a, TxtLookup> {
+        Box::pin(async move {
+            let response = self.query(name, RecordType::TXT).await?;
+            let records = match response.response_code() {
+                // the question has been compared with the request already
+                ResponseCode::NoError => response.query().map_or_else(Vec::new, |query| {
+                    txt_records(query.name(), response.answers())
+                }),
+                ResponseCode::NXDomain => Vec::new(),
+                code => return Err(DnsError::Query(format!("{name}: {code}"))),
+            };
+            let dnssec = if response.authentic_data() {
+                DnssecStatus::Secure
+            } else {
+                DnssecStatus::Insecure
+            };
+            Ok(TxtLookup { records, dnssec })
+        })
+    }
+}
+
+/// Returns `true` if the message is a response to the request, i.e. it has the same ID and repeats its question.
+fn answers(request: &Message, response: &Message) -> bool {
+    response.id() == request.id()
+        && response.message_type() == MessageType::Response
+        && response.queries() == request.queries()
+}
+
+/// Returns the TXT records of the answers that are owned by the name, directly or via a chain of CNAME records.
+/// Records of other names are ignored, a server must not be able to add records for names it hasn't been asked for.
+fn txt_records(name: &Name, answers: &[Record]) -> Vec<String> {
+    let mut names = vec![name];
+    // the records of a chain may appear in any order
+    while let Some(target) = answers.iter().find_map(|record| match record.data() {
+        RData::CNAME(cname) if names.contains(&record.name()) && !names.contains(&&cname.0) => {
+            Some(&cname.0)
+        }
+        _ => None,
+    }) {
+        names.push(target);
+    }
+    answers
+        .iter()
+        .filter(|record| names.contains(&record.name()))
+        .filter_map(|record| match record.data() {
+            RData::TXT(txt) => Some(
+                txt.txt_data()
+                    .iter()
+                    .map(|data| String::from_utf8_lossy(data))
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use hickory_proto::rr::rdata::{CNAME, TXT};
+
+    use super::*;
+
+    /// Serves the responses that `respond` returns for a request via UDP and TCP on the same port. All responses are
+    /// sent via UDP, only the first one via TCP. The second argument of `respond` is `true` for requests via UDP.
+    async fn serve<F>(respond: F) -> SocketAddr
+    where
+        F: Fn(&Message, bool) -> Vec<Message> + Clone + Send + 'static,
+    {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = socket.local_addr().unwrap();
+        let listener = tokio::net::TcpListener::bind(server).await.unwrap();
+        let respond_tcp = respond.clone();
+        tokio::spawn(async move {
+            let mut buffer = vec![0; 4096];
+            while let Ok((length, peer)) = socket.recv_from(&mut buffer).await {
+                let request = Message::from_vec(&buffer[..length]).unwrap();
+                for response in respond(&request, true) {
+                    socket
+                        .send_to(&response.to_vec().unwrap(), peer)
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let length = stream.read_u16().await.unwrap();
+                let mut request = vec![0; usize::from(length)];
+                stream.read_exact(&mut request).await.unwrap();
+                let request = Message::from_vec(&request).unwrap();
+                let response = respond_tcp(&request, false).remove(0).to_vec().unwrap();
+                stream
+                    .write_all(&u16::try_from(response.len()).unwrap().to_be_bytes())
+                    .await
+                    .unwrap();
+                stream.write_all(&response).await.unwrap();
+            }
+        });
+        server
+    }
+
+    /// Returns an empty response to the request that repeats the given question.
+    fn response(request: &Message, query: Query, authenticated: bool) -> Message {
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(OpCode::Query)
+            .set_authentic_data(authenticated)
+            .add_query(query);
+        response
+    }
+
+    fn txt(name: &str, value: &str) -> Record {
+        Record::from_rdata(
+            Name::from_ascii(name).unwrap(),
+            300,
+            RData::TXT(TXT::new(vec![value.to_owned()])),
+        )
+    }
+
+    /// Serves the given TXT records. Names of the zone that are not listed don't exist. The Authenticated Data flag
+    /// is set if `authenticated` is `true`. Responses with more than one record are truncated via UDP.
+    async fn serve_dns(
+        records: HashMap<&'static str, Vec<&'static str>>,
+        authenticated: bool,
+    ) -> SocketAddr {
+        serve(move |request, udp| {
+            let query = request.queries()[0].clone();
+            let name = query.name().to_ascii();
+            let mut response = response(request, query, authenticated);
+            match records.get(name.as_str()) {
+                Some(values) if udp && values.len() > 1 => {
+                    response.set_truncated(true);
+                }
+                Some(values) => {
+                    for value in values {
+                        response.add_answer(txt(&name, value));
+                    }
+                }
+                None => {
+                    response.set_response_code(ResponseCode::NXDomain);
+                }
+            }
+            vec![response]
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn txt_lookup() {
+        let server = serve_dns(
+            HashMap::from([
+                ("_did.example.com.", vec!["did:web:example.com"]),
+                (
+                    "_did.multiple.example.com.",
+                    vec!["did:web:example.com", "did:web:shop.example.com"],
+                ),
+            ]),
+            false,
+        )
+        .await;
+        let resolver = StubResolver::new(server);
+
+        let lookup = resolver.txt_lookup("_did.example.com.").await.unwrap();
+        assert_eq!(lookup.records, ["did:web:example.com"]);
+        assert_eq!(lookup.dnssec, DnssecStatus::Insecure);
+
+        // truncated responses are repeated via TCP
+        let lookup = resolver
+            .txt_lookup("_did.multiple.example.com.")
+            .await
+            .unwrap();
+        assert_eq!(
+            lookup.records,
+            ["did:web:example.com", "did:web:shop.example.com"]
+        );
+
+        let lookup = resolver
+            .txt_lookup("_did.unknown.example.com.")
+            .await
+            .unwrap();
+        assert!(lookup.records.is_empty());
+    }
+
+    #[tokio::test]
+    async fn txt_lookup_authenticated() {
+        let server = serve_dns(
+            HashMap::from([("_did.example.com.", vec!["did:web:example.com"])]),
+            true,
+        )
+        .await;
+        let lookup = StubResolver::new(server)
+            .txt_lookup("_did.example.com.")
+            .await
+            .unwrap();
+        assert_eq!(lookup.dnssec, DnssecStatus::Secure);
+    }
+
+    #[tokio::test]
+    async fn txt_lookup_other_question() {
+        // a response to another question with the same ID is ignored via UDP
+        let server = serve(|request, _| {
+            let query = request.queries()[0].clone();
+            let other = Query::query(
+                Name::from_ascii("_did.attacker.example.").unwrap(),
+                RecordType::TXT,
+            );
+            let mut spoofed = response(request, other, false);
+            spoofed.add_answer(txt("_did.attacker.example.", "did:web:attacker.example"));
+            let mut response = response(request, query, false);
+            response.add_answer(txt("_did.example.com.", "did:web:example.com"));
+            vec![spoofed, response]
+        })
+        .await;
+        let resolver = StubResolver::new(server);
+        let lookup = resolver.txt_lookup("_did.Example.com").await.unwrap();
+        assert_eq!(lookup.records, ["did:web:example.com"]);
+
+        // and rejected via TCP
+        let server = serve(|request, udp| {
+            let mut query = request.queries()[0].clone();
+            if !udp {
+                query.set_query_type(RecordType::A);
+            }
+            let mut response = response(request, query, false);
+            response.set_truncated(udp);
+            vec![response]
+        })
+        .await;
+        assert!(matches!(
+            StubResolver::new(server)
+                .txt_lookup("_did.example.com.")
+                .await,
+            Err(DnsError::Query(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn txt_lookup_owner_names() {
+        let server = serve(|request, _| {
+            let mut response = response(request, request.queries()[0].clone(), false);
+            response
+                .add_answer(txt("_did.example.com.", "did:web:example.com"))
+                .add_answer(txt("_did.target.example.", "did:web:target.example"))
+                .add_answer(txt("_did.attacker.example.", "did:web:attacker.example"))
+                .add_answer(Record::from_rdata(
+                    Name::from_ascii("_did.alias.example.").unwrap(),
+                    300,
+                    RData::CNAME(CNAME(Name::from_ascii("_did.target.example.").unwrap())),
+                ))
+                .add_answer(Record::from_rdata(
+                    Name::from_ascii("_did.example.com.").unwrap(),
+                    300,
+                    RData::CNAME(CNAME(Name::from_ascii("_did.alias.example.").unwrap())),
+                ));
+            vec![response]
+        })
+        .await;
+        let lookup = StubResolver::new(server)
+            .txt_lookup("_did.example.com.")
+            .await
+            .unwrap();
+        // records of names outside the CNAME chain are ignored
+        assert_eq!(
+            lookup.records,
+            ["did:web:example.com", "did:web:target.example"]
+        );
+    }
+
+    #[tokio::test]
+    async fn txt_lookup_timeout() {
+        // server that never responds
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let resolver = StubResolver::new(socket.local_addr().unwrap())
+            .with_timeout(Duration::from_millis(100));
+        assert!(matches!(
+            resolver.txt_lookup("_did.example.com.").await,
+            Err(DnsError::Timeout(_))
+        ));
+    }
+
+    #[test]
+    fn resolv_conf() {
+        let path = std::env::temp_dir().join(format!("resolv-{}.conf", std::process::id()));
+        fs::write(
+            &path,
+            "# comment\nsearch example.com\nnameserver invalid\nnameserver 10.0.0.1\nnameserver 10.0.0.2\n",
+        )
+        .unwrap();
+        let resolver = StubResolver::from_resolv_conf(&path).unwrap();
+        assert_eq!(resolver.server, "10.0.0.1:53".parse().unwrap());
+
+        fs::write(&path, "search example.com\n").unwrap();
+        assert!(matches!(
+            StubResolver::from_resolv_conf(&path),
+            Err(DnsError::Config(_))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
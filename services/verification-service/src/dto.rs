@@ -1,22 +1,74 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+
 use axum::{
-    extract::{rejection::QueryRejection, FromRequestParts, Query},
-    http::{request::Parts, StatusCode},
+    extract::{FromRequest, FromRequestParts, Multipart, Query, Request},
+    http::{header::CONTENT_TYPE, request::Parts, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
 use ssi::{
-    claims::{vc::v1::SpecializedJsonCredential, Invalid, InvalidClaims, InvalidProof},
-    dids::Document,
+    claims::{
+        vc::v1::{JsonPresentation, SpecializedJsonCredential},
+        Invalid, InvalidClaims, InvalidProof,
+    },
+    dids::{resolution::Error as ResolutionError, DIDBuf, Document},
 };
 use url::Url;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::identity::IdentitySummary;
+use crate::impersonation::Impersonation;
+use crate::lists::ListMatch;
+use crate::policy::PolicyEvaluation;
+use crate::presentation::PresentationFormat;
+use crate::resolver::{DocumentMetadata, ResolutionMetadata};
 
+/// Verification result, either complete or shortened.
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
 #[non_exhaustive]
 pub enum VerificationResponse {
-    OK(VerificationResponseDto),
+    OK(Box<VerificationResponseDto>),
+    Brief(BriefVerificationResponseDto),
 }
 
 impl IntoResponse for VerificationResponse {
+    #[inline]
+    fn into_response(self) -> Response {
+        match self {
+            Self::OK(data) => (StatusCode::OK, Json(data)).into_response(),
+            Self::Brief(data) => (StatusCode::OK, Json(data)).into_response(),
+        }
+    }
+}
+
+/// Verification result of API version 2, either grouped or shortened.
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+#[non_exhaustive]
+pub enum GroupedVerificationResponse {
+    OK(Box<GroupedVerificationResponseDto>),
+    Brief(BriefVerificationResponseDto),
+}
+
+impl IntoResponse for GroupedVerificationResponse {
+    #[inline]
+    fn into_response(self) -> Response {
+        match self {
+            Self::OK(data) => (StatusCode::OK, Json(data)).into_response(),
+            Self::Brief(data) => (StatusCode::OK, Json(data)).into_response(),
+        }
+    }
+}
+
+#[non_exhaustive]
+pub enum DiagnosisResponse {
+    OK(DiagnosisResponseDto),
+}
+
+impl IntoResponse for DiagnosisResponse {
     #[inline]
     fn into_response(self) -> Response {
         match self {
@@ -25,93 +77,756 @@ impl IntoResponse for VerificationResponse {
     }
 }
 
+/// Omits internal details, e.g. upstream error messages, from error responses if set.
+static PRODUCTION_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the production mode. In production mode, error responses only contain the public description
+/// of the problem and no internal details.
+#[inline]
+pub fn set_production_mode(enabled: bool) {
+    PRODUCTION_MODE.store(enabled, Ordering::Relaxed);
+}
+
+/// Class of a failed request. Every class is identified by a stable URI that is returned as `type` of the problem
+/// details, see <https://www.rfc-editor.org/rfc/rfc9457>.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
 #[non_exhaustive]
-pub enum VerificationError {
-    BadRequestJson(VerificationErrorResponseDto),
-    NotFoundJson(VerificationResponseDto),
-    VerificationImpossible(VerificationErrorResponseDto),
+pub enum ProblemType {
+    /// The query or request body is invalid.
+    InvalidQuery,
+    /// A DID could not be resolved.
+    ResolutionFailure,
+    /// The DID configuration of the domain is invalid.
+    DidConfigInvalid,
+    /// An upstream server didn't respond in time.
+    UpstreamTimeout,
+    /// Unexpected internal error.
+    Internal,
+}
+
+impl ProblemType {
+    /// URI that identifies the problem type.
+    #[inline]
+    #[must_use]
+    pub const fn uri(self) -> &'static str {
+        match self {
+            Self::InvalidQuery => "https://check.identinet.io/problems/invalid-query",
+            Self::ResolutionFailure => "https://check.identinet.io/problems/resolution-failure",
+            Self::DidConfigInvalid => "https://check.identinet.io/problems/did-config-invalid",
+            Self::UpstreamTimeout => "https://check.identinet.io/problems/upstream-timeout",
+            Self::Internal => "https://check.identinet.io/problems/internal",
+        }
+    }
+
+    /// Short, human-readable summary of the problem type.
+    #[inline]
+    #[must_use]
+    pub const fn title(self) -> &'static str {
+        match self {
+            Self::InvalidQuery => "Invalid query",
+            Self::ResolutionFailure => "DID resolution failed",
+            Self::DidConfigInvalid => "DID configuration invalid",
+            Self::UpstreamTimeout => "Upstream timeout",
+            Self::Internal => "Internal error",
+        }
+    }
+}
+
+/// (De)serializes status codes as numbers, e.g. when errors are cached.
+mod status_code {
+    use axum::http::StatusCode;
+    use serde::{de::Error as _, Deserialize as _, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        status: &StatusCode,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(status.as_u16())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<StatusCode, D::Error> {
+        StatusCode::from_u16(u16::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+/// Failed request, rendered as `application/problem+json`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[non_exhaustive]
+pub struct VerificationError {
+    pub problem_type: ProblemType,
+    #[serde(with = "status_code")]
+    pub status: StatusCode,
+    /// Public description of the problem.
+    pub detail: String,
+    /// Internal details, e.g. upstream error messages. Replace `detail` unless the production mode is enabled.
+    pub internal_detail: Option<String>,
+    /// Verification result that caused the problem.
+    pub result: Option<VerificationResult>,
 }
 
 impl VerificationError {
+    #[inline]
+    #[must_use]
+    pub const fn new(problem_type: ProblemType, status: StatusCode, detail: String) -> Self {
+        Self {
+            problem_type,
+            status,
+            detail,
+            internal_detail: None,
+            result: None,
+        }
+    }
+
     #[inline]
     #[must_use]
     pub fn bad_request(message: &str) -> Self {
-        let error = VerificationErrorResponseDto {
-            error: message.to_owned(),
-            verified: false,
-        };
-        Self::BadRequestJson(error)
+        Self::bad_request_from(message.to_owned())
     }
 
     #[inline]
     #[must_use]
     pub const fn bad_request_from(message: String) -> Self {
-        let error = VerificationErrorResponseDto {
-            error: message,
-            verified: false,
+        Self::new(ProblemType::InvalidQuery, StatusCode::BAD_REQUEST, message)
+    }
+
+    /// DID resolution failed. Unknown DIDs result in `404`, failures of the resolver in `502`.
+    #[inline]
+    #[must_use]
+    pub fn resolution_failure(error: &ResolutionError) -> Self {
+        let status = match error {
+            ResolutionError::NotFound => StatusCode::NOT_FOUND,
+            ResolutionError::Internal(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::UNPROCESSABLE_ENTITY,
         };
-        Self::BadRequestJson(error)
+        Self::new(
+            ProblemType::ResolutionFailure,
+            status,
+            "DID could not be resolved.".to_owned(),
+        )
+        .with_internal_detail(error.to_string())
     }
 
-    // fn not_found(message: &str) -> Self {
-    //     Self::not_found_from(message.to_string())
-    // }
+    /// The DID configuration couldn't be verified, thus the verification is impossible.
+    #[inline]
+    #[must_use]
+    pub fn did_config_invalid(result: VerificationResult) -> Self {
+        let payload = result.payload();
+        let mut error = Self::new(
+            ProblemType::DidConfigInvalid,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            payload.message.clone(),
+        )
+        .with_internal_detail(payload.details.clone());
+        error.result = Some(result);
+        error
+    }
 
     #[inline]
     #[must_use]
-    pub fn not_found_from(_message: String) -> Self {
-        let empty = VerificationResponseDto {
-            documents: Vec::new(),
-            credentials: Vec::new(),
-            results: Vec::new(),
-            verified: false,
-        };
-        Self::NotFoundJson(empty)
+    pub fn upstream_timeout() -> Self {
+        Self::new(
+            ProblemType::UpstreamTimeout,
+            StatusCode::GATEWAY_TIMEOUT,
+            "An upstream server didn't respond in time.".to_owned(),
+        )
     }
 
     #[inline]
     #[must_use]
-    pub const fn verification_impossible_from(message: String) -> Self {
-        let empty = VerificationErrorResponseDto {
-            error: message,
-            verified: false,
+    pub fn internal() -> Self {
+        Self::new(
+            ProblemType::Internal,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Unexpected error.".to_owned(),
+        )
+    }
+
+    /// Attaches internal details that are omitted in production mode.
+    #[inline]
+    #[must_use]
+    pub fn with_internal_detail(mut self, detail: String) -> Self {
+        self.internal_detail = Some(detail);
+        self
+    }
+
+    /// Converts the error into problem details. Internal details are omitted in production mode.
+    #[inline]
+    #[must_use]
+    pub fn to_problem(&self, production: bool) -> ProblemDetails {
+        let detail = match &self.internal_detail {
+            Some(internal_detail) if !production => internal_detail.clone(),
+            _ => self.detail.clone(),
         };
-        Self::VerificationImpossible(empty)
+        ProblemDetails {
+            type_: self.problem_type.uri().to_owned(),
+            title: self.problem_type.title().to_owned(),
+            status: self.status.as_u16(),
+            detail: Some(detail),
+            code: self.result.as_ref().map(|result| result.payload().code),
+            result: self.result.as_ref().map(|result| result.name().to_owned()),
+            verified: false,
+        }
     }
 }
 
 impl IntoResponse for VerificationError {
     #[inline]
     fn into_response(self) -> Response {
-        match self {
-            Self::BadRequestJson(data) => (StatusCode::BAD_REQUEST, Json(data)).into_response(),
-            Self::NotFoundJson(data) => (StatusCode::NOT_FOUND, Json(data)).into_response(),
-            Self::VerificationImpossible(data) => (StatusCode::OK, Json(data)).into_response(),
+        let problem = self.to_problem(PRODUCTION_MODE.load(Ordering::Relaxed));
+        (
+            self.status,
+            [(CONTENT_TYPE, "application/problem+json")],
+            Json(problem),
+        )
+            .into_response()
+    }
+}
+
+/// Method that discovered the DIDs of a URL.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum DiscoveryMethod {
+    /// Path-based did:web DID of the URL, e.g. `did:web:example.com:shops:alice`.
+    DidWebPath,
+    /// DIF Well-Known DID Configuration of the origin.
+    DidConfiguration,
+    /// `_did.<domain>` TXT records of the domain.
+    Dns,
+    /// `<link rel="did">`, `<meta name="did">` or JSON-LD `sameAs` declarations of the HTML page.
+    Html,
+    /// did:web DID of the domain.
+    DidWeb,
+}
+
+/// Graded trust in a verified URL or DID, derived from the checks that passed. The levels build on each other.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, ToSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum TrustLevel {
+    /// No check passed that establishes trust.
+    #[default]
+    None,
+    /// The origin is linked to an active DID.
+    DomainLinked,
+    /// All credentials are valid and at least one has been issued by a third party.
+    IdentityVerified,
+    /// Like `identity-verified` with a credential of a trusted issuer.
+    TrustedIssuerVerified,
+}
+
+/// How the DIDs of a URL have been discovered.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+pub struct Discovery {
+    pub method: DiscoveryMethod,
+    /// `true` if the DNS records have been validated with DNSSEC. Only present for the `dns` method.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dnssec: Option<bool>,
+}
+
+impl Discovery {
+    #[inline]
+    #[must_use]
+    pub const fn new(method: DiscoveryMethod) -> Self {
+        Self {
+            method,
+            dnssec: None,
         }
     }
 }
 
-#[derive(Serialize)]
+/// Verification result.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct VerificationResponseDto {
+    /// OR-combined codes of all verification results, including the results of DIDs and presentations.
+    #[serde(default)]
+    pub code: u32,
+    /// List of Verifiable Credentials listed by Linked Verifiable Presentations associated with the given URL.
+    #[schema(value_type = Vec<Object>)]
     pub credentials: Vec<SpecializedJsonCredential>,
+    /// Method that discovered the DIDs. Absent if a DID has been verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovery: Option<Discovery>,
+    /// List of DID documents associated with the given URL or DID.
+    #[schema(value_type = Vec<Object>)]
     pub documents: Vec<Document>,
+    /// Metadata of the DID documents, aligned with `documents` by index.
+    #[serde(default, rename = "didDocumentMetadata")]
+    pub document_metadata: Vec<DocumentMetadata>,
+    /// Operator of the URL or DID, extracted from the credentials that have been verified successfully.
+    #[serde(default, skip_serializing_if = "IdentitySummary::is_empty")]
+    pub identity: IdentitySummary,
+    /// Suspicion that the origin is a lookalike of a protected domain or of a domain claimed in the credentials.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonation: Option<Impersonation>,
+    /// Entry of the allow or block lists that matched the origin, a DID or an issuer. Block entries take precedence.
+    #[serde(default, rename = "listMatch", skip_serializing_if = "Option::is_none")]
+    pub list_match: Option<ListMatch>,
+    /// Normalized origin that has been verified, e.g. `https://xn--bcher-kva.example` for `bücher.example`. Absent if
+    /// a DID has been verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Evaluation against the policy selected by the `policy` query parameter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PolicyEvaluation>,
+    /// Detailed list of verification results per credential.
     pub results: Vec<VerificationResult>,
+    /// Graded trust derived from the checks that passed.
+    #[serde(default, rename = "trustLevel")]
+    pub trust_level: TrustLevel,
+    /// `true` if all credentials have been verified successfully.
+    pub verified: bool,
+    /// Findings that don't affect the verification result, e.g. credentials that expire soon or unreachable
+    /// endpoints.
+    #[serde(default)]
+    pub warnings: Vec<Finding>,
+}
+
+impl From<&GroupedVerificationResponseDto> for VerificationResponseDto {
+    /// Flattens the grouped result. Credentials and results are aligned by index.
+    #[inline]
+    fn from(dto: &GroupedVerificationResponseDto) -> Self {
+        Self {
+            code: dto.code,
+            credentials: dto
+                .credentials()
+                .map(|credential| credential.credential.clone())
+                .collect(),
+            discovery: dto.discovery.clone(),
+            documents: dto.dids.iter().map(|did| did.document.clone()).collect(),
+            document_metadata: dto
+                .dids
+                .iter()
+                .map(|did| did.document_metadata.clone())
+                .collect(),
+            identity: dto.identity.clone(),
+            impersonation: dto.impersonation.clone(),
+            list_match: dto.list_match.clone(),
+            origin: dto.origin.clone(),
+            policy: dto.policy.clone(),
+            results: dto
+                .credentials()
+                .map(|credential| credential.result.clone())
+                .collect(),
+            trust_level: dto.trust_level,
+            verified: dto.verified,
+            warnings: dto.warnings().cloned().collect(),
+        }
+    }
+}
+
+/// Verification result grouped per DID, Linked Verifiable Presentation service and presentation.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct GroupedVerificationResponseDto {
+    /// OR-combined codes of all verification results, including the results of DIDs and presentations.
+    #[serde(default)]
+    pub code: u32,
+    /// DIDs associated with the given URL or DID in the order of their discovery.
+    pub dids: Vec<DidVerificationDto>,
+    /// Method that discovered the DIDs. Absent if a DID has been verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovery: Option<Discovery>,
+    /// Operator of the URL or DID, extracted from the credentials that have been verified successfully.
+    #[serde(default, skip_serializing_if = "IdentitySummary::is_empty")]
+    pub identity: IdentitySummary,
+    /// Suspicion that the origin is a lookalike of a protected domain or of a domain claimed in the credentials.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonation: Option<Impersonation>,
+    /// Entry of the allow or block lists that matched the origin, a DID or an issuer. Block entries take precedence.
+    #[serde(default, rename = "listMatch", skip_serializing_if = "Option::is_none")]
+    pub list_match: Option<ListMatch>,
+    /// Normalized origin that has been verified. Absent if a DID has been verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Evaluation against the policy selected by the `policy` query parameter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PolicyEvaluation>,
+    /// Graded trust derived from the checks that passed.
+    #[serde(default, rename = "trustLevel")]
+    pub trust_level: TrustLevel,
+    /// `true` if all credentials have been verified successfully.
     pub verified: bool,
 }
 
+impl GroupedVerificationResponseDto {
+    /// Returns the OR-combined codes of the results of all DIDs, presentations and credentials.
+    #[inline]
+    #[must_use]
+    pub fn results_code(&self) -> u32 {
+        let dids = self.dids.iter().filter_map(|did| did.result.as_ref());
+        let services = self
+            .dids
+            .iter()
+            .flat_map(|did| &did.services)
+            .filter_map(|service| service.result.as_ref());
+        let presentations = self
+            .dids
+            .iter()
+            .flat_map(|did| &did.services)
+            .flat_map(|service| &service.presentations)
+            .map(|presentation| &presentation.result);
+        let credentials = self.credentials().map(|credential| &credential.result);
+        let impersonation = self
+            .impersonation
+            .iter()
+            .map(|impersonation| &impersonation.result);
+        let list_match = self
+            .list_match
+            .iter()
+            .filter_map(|list_match| list_match.result.as_ref());
+        dids.chain(services)
+            .chain(presentations)
+            .chain(credentials)
+            .chain(impersonation)
+            .chain(list_match)
+            .fold(0, |code, result| code | result.payload().code)
+    }
+
+    /// Returns all credentials of all presentations.
+    #[inline]
+    pub fn credentials(&self) -> impl Iterator<Item = &CredentialVerificationDto> {
+        self.dids
+            .iter()
+            .flat_map(|did| &did.services)
+            .flat_map(|service| &service.presentations)
+            .flat_map(|presentation| &presentation.credentials)
+    }
+
+    /// Returns the credentials that have been verified successfully and belong to an active DID, together with
+    /// their index in [`Self::credentials`] and the DID of their holder.
+    #[inline]
+    pub fn valid_credentials(
+        &self,
+    ) -> impl Iterator<Item = (usize, &str, &SpecializedJsonCredential)> {
+        self.dids
+            .iter()
+            .flat_map(|did| {
+                did.services
+                    .iter()
+                    .flat_map(|service| &service.presentations)
+                    .flat_map(|presentation| &presentation.credentials)
+                    .map(move |credential| (did, credential))
+            })
+            .enumerate()
+            .filter(|(_, (did, credential))| {
+                did.result.is_none() && matches!(credential.result, VerificationResult::VcValid(_))
+            })
+            .map(|(index, (did, credential))| {
+                (index, did.document.id.as_str(), &credential.credential)
+            })
+    }
+
+    /// Returns the warnings of all services, presentations and credentials.
+    #[inline]
+    pub fn warnings(&self) -> impl Iterator<Item = &Finding> {
+        self.dids
+            .iter()
+            .flat_map(|did| &did.services)
+            .flat_map(|service| {
+                service
+                    .warnings
+                    .iter()
+                    .chain(service.presentations.iter().flat_map(|presentation| {
+                        presentation.warnings.iter().chain(
+                            presentation
+                                .credentials
+                                .iter()
+                                .flat_map(|credential| &credential.warnings),
+                        )
+                    }))
+            })
+    }
+}
+
+/// DID document and its Linked Verifiable Presentations.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct DidVerificationDto {
+    #[schema(value_type = Object)]
+    pub document: Document,
+    /// Metadata of the DID document, e.g. whether the DID has been deactivated and when it has been updated.
+    #[serde(default, rename = "didDocumentMetadata")]
+    pub document_metadata: DocumentMetadata,
+    /// Metadata of the resolution, e.g. the media type of the DID document.
+    #[serde(default, rename = "didResolutionMetadata")]
+    pub resolution_metadata: ResolutionMetadata,
+    /// Failure of the DID itself, e.g. `DidDeactivated`. It applies to all credentials of the DID. Absent if the DID
+    /// is active.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<VerificationResult>,
+    /// `LinkedVerifiablePresentation` services of the DID document in the order of the document.
+    pub services: Vec<LinkedPresentationServiceDto>,
+}
+
+/// Linked Verifiable Presentation service and the presentations that have been fetched from it.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct LinkedPresentationServiceDto {
+    /// Endpoint the presentations have been fetched from. Absent if no endpoint responded with a presentation in time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// ID of the service, e.g. `did:web:example.com#linked-vp`.
+    pub id: String,
+    pub presentations: Vec<PresentationVerificationDto>,
+    /// `VpParseError` if endpoints responded but none of them with presentations. Absent otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<VerificationResult>,
+    /// Findings of the service, e.g. endpoints that couldn't be fetched.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Finding>,
+}
+
+/// Linked Verifiable Presentation and the verification results of its credentials.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct PresentationVerificationDto {
+    pub credentials: Vec<CredentialVerificationDto>,
+    /// Encoding the endpoint served the presentation in. Presentations in JWT form are decoded.
+    #[serde(default)]
+    pub format: PresentationFormat,
+    #[schema(value_type = Object)]
+    pub presentation: JsonPresentation,
+    /// Result of the presentation's proof and holder, `VpValid` if both are valid. A failure applies to all
+    /// credentials of the presentation.
+    pub result: VerificationResult,
+    /// Findings of the presentation that don't affect its result, e.g. legacy proof types.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Finding>,
+}
+
+/// Verifiable Credential and its verification result.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct CredentialVerificationDto {
+    #[schema(value_type = Object)]
+    pub credential: SpecializedJsonCredential,
+    pub result: VerificationResult,
+    /// Findings of the credential that don't affect its result, e.g. an expiration date in the near future.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Finding>,
+}
+
+/// Shortened verification result that only contains the verdict and a summary per credential.
+#[derive(Serialize, ToSchema)]
+#[non_exhaustive]
+pub struct BriefVerificationResponseDto {
+    /// OR-combined codes of all verification results.
+    pub code: u32,
+    pub credentials: Vec<CredentialSummary>,
+    /// Normalized origin that has been verified. Absent if a DID has been verified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Evaluation against the policy selected by the `policy` query parameter.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PolicyEvaluation>,
+    /// Graded trust derived from the checks that passed.
+    #[serde(rename = "trustLevel")]
+    pub trust_level: TrustLevel,
+    pub verified: bool,
+}
+
+/// Summary of a credential and its verification result.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct CredentialSummary {
+    pub code: u32,
+    /// DID of the issuer.
+    pub issuer: Option<String>,
+    /// Name of the verification result, e.g. `VcValid`.
+    #[schema(value_type = String)]
+    pub result: &'static str,
+    /// Value of `credentialSubject.name`, if present.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject_name: Option<String>,
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+    pub verified: bool,
+}
+
+impl CredentialSummary {
+    /// Summarizes the given credential and its verification result.
+    #[inline]
+    #[must_use]
+    pub fn new(credential: &SpecializedJsonCredential, result: &VerificationResult) -> Self {
+        let json = serde_json::to_value(credential).unwrap_or_default();
+        let types = match json.get("type") {
+            Some(serde_json::Value::String(type_)) => vec![type_.clone()],
+            Some(serde_json::Value::Array(types)) => types
+                .iter()
+                .filter_map(|type_| type_.as_str().map(ToOwned::to_owned))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let issuer = match json.get("issuer") {
+            Some(serde_json::Value::String(issuer)) => Some(issuer.clone()),
+            Some(issuer) => issuer
+                .get("id")
+                .and_then(serde_json::Value::as_str)
+                .map(ToOwned::to_owned),
+            None => None,
+        };
+        let subject = match json.get("credentialSubject") {
+            Some(serde_json::Value::Array(subjects)) => subjects.first(),
+            subject => subject,
+        };
+        let subject_name = subject
+            .and_then(|subject| subject.get("name"))
+            .and_then(serde_json::Value::as_str)
+            .map(ToOwned::to_owned);
+        let payload = result.payload();
+        Self {
+            code: payload.code,
+            issuer,
+            result: result.name(),
+            subject_name,
+            types,
+            verified: payload.verified,
+        }
+    }
+}
+
+impl From<&VerificationResponseDto> for BriefVerificationResponseDto {
+    #[inline]
+    fn from(dto: &VerificationResponseDto) -> Self {
+        Self {
+            code: dto.code,
+            credentials: dto
+                .credentials
+                .iter()
+                .zip(&dto.results)
+                .map(|(credential, result)| CredentialSummary::new(credential, result))
+                .collect(),
+            origin: dto.origin.clone(),
+            policy: dto.policy.clone(),
+            trust_level: dto.trust_level,
+            verified: dto.verified,
+        }
+    }
+}
+
 // TODO deserialize is only required during controller tests - can we conditionally derive?
-#[derive(Serialize, Deserialize)]
+/// Problem details for HTTP APIs, see <https://www.rfc-editor.org/rfc/rfc9457>.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 #[non_exhaustive]
-pub struct VerificationErrorResponseDto {
-    pub error: String,
+pub struct ProblemDetails {
+    /// URI that identifies the problem type, e.g. `https://check.identinet.io/problems/invalid-query`.
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Code of the verification result that caused the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<u32>,
+    /// Name of the verification result that caused the problem, e.g. `DidConfigError`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    /// Always `false`.
     pub verified: bool,
 }
 
+/// Severity of a finding.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A single result of the deployment diagnosis, including a suggestion how to fix the issue, or a warning of a
+/// verification that doesn't affect its result.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[non_exhaustive]
+pub struct Finding {
+    /// Stable identifier of the check, e.g. `well_known.cors`.
+    pub code: String,
+    pub severity: Severity,
+    /// URL or DID of the resource the finding relates to.
+    pub resource: String,
+    pub message: String,
+    /// Suggestion how to fix the issue.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    #[inline]
+    #[must_use]
+    pub fn new(severity: Severity, code: &str, resource: &str, message: String) -> Self {
+        Self {
+            code: code.to_owned(),
+            severity,
+            resource: resource.to_owned(),
+            message,
+            fix: None,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn error(code: &str, resource: &str, message: String) -> Self {
+        Self::new(Severity::Error, code, resource, message)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn warning(code: &str, resource: &str, message: String) -> Self {
+        Self::new(Severity::Warning, code, resource, message)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn info(code: &str, resource: &str, message: String) -> Self {
+        Self::new(Severity::Info, code, resource, message)
+    }
+
+    /// Attaches a remediation hint to the finding.
+    #[inline]
+    #[must_use]
+    pub fn with_fix(mut self, fix: String) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+/// Result of the deployment diagnosis.
+#[derive(Serialize, ToSchema)]
+#[non_exhaustive]
+pub struct DiagnosisResponseDto {
+    pub findings: Vec<Finding>,
+    /// `true` if no finding has severity `error`.
+    pub passed: bool,
+}
+
+impl DiagnosisResponseDto {
+    #[inline]
+    #[must_use]
+    pub fn from_findings(findings: Vec<Finding>) -> Self {
+        let passed = !findings
+            .iter()
+            .any(|finding| finding.severity == Severity::Error);
+        Self { findings, passed }
+    }
+}
+
 // TODO Debug is only required during tests - can we conditionally derive?
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
 #[non_exhaustive]
 pub struct VerificationRequest {
+    /// Domain name, HTTPS URL or DID to verify, e.g. `example.com`, `https://bücher.example` or `did:web:example.com`.
     pub q: String,
+    /// Return a shortened version of the verification result.
+    #[serde(default)]
+    pub brief: bool,
+    /// Name of a policy the verification is evaluated against, e.g. `marketplace`. The result is only verified if all
+    /// rules of the policy passed.
+    pub policy: Option<String>,
 }
 
 impl<S> FromRequestParts<S> for VerificationRequest
@@ -123,34 +838,130 @@ where
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let query = Query::<Self>::from_request_parts(parts, state)
             .await
-            .map_err(|err| match err {
-                QueryRejection::FailedToDeserializeQueryString(
-                    failed_to_deserialize_query_string,
-                ) => {
-                    // TODO mute in prod
-                    VerificationError::bad_request_from(
-                        failed_to_deserialize_query_string.to_string(),
-                    )
-                }
-                _ => VerificationError::bad_request(
-                    "Failed to deserialize query string: unknown error",
-                ),
+            .map_err(|err| {
+                VerificationError::bad_request("Failed to deserialize query string")
+                    .with_internal_detail(err.body_text())
             })?;
 
         if query.q.is_empty() {
-            return Err(VerificationError::bad_request("empty 'q' param")); // TODO mute in prod
+            return Err(VerificationError::bad_request("empty 'q' param"));
         }
 
-        if !is_valid_url(&query.q) {
-            return Err(VerificationError::bad_request("invalid 'q' param"));
-            // TODO mute in prod
+        let mut query = query.0;
+        query.q = normalize_query(&query.q)
+            .ok_or_else(|| VerificationError::bad_request("invalid 'q' param"))?
+            .to_string();
+
+        Ok(query)
+    }
+}
+
+/// Artifacts that shall be verified before they are published.
+///
+/// Accepted as JSON or as `multipart/form-data` with one part per property. The parts `didDocument`,
+/// `didDocumentMetadata`, `didConfiguration` and `linkedPresentations` contain JSON documents.
+#[derive(Deserialize, Debug, Default, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct DryRunRequest {
+    /// HTTPS origin the artifacts will be published at, e.g. `https://example.com`.
+    pub origin: String,
+    /// DID document, takes precedence over a published DID document with the same id.
+    #[schema(value_type = Option<Object>)]
+    pub did_document: Option<serde_json::Value>,
+    /// Metadata of the DID document, e.g. `{"deactivated": true}` to test how a deactivated DID is reported.
+    pub did_document_metadata: Option<DocumentMetadata>,
+    /// Content of `/.well-known/did-configuration.json`.
+    #[schema(value_type = Option<Object>)]
+    pub did_configuration: Option<serde_json::Value>,
+    /// Linked Verifiable Presentations by service endpoint URL.
+    #[serde(default)]
+    #[schema(value_type = HashMap<String, Object>)]
+    pub linked_presentations: HashMap<String, serde_json::Value>,
+}
+
+impl DryRunRequest {
+    /// Reads the bundle from the parts of a `multipart/form-data` request.
+    async fn from_multipart(mut multipart: Multipart) -> Result<Self, VerificationError> {
+        let mut bundle = Self::default();
+        while let Some(field) = multipart.next_field().await.map_err(|e| {
+            VerificationError::bad_request("invalid request body")
+                .with_internal_detail(e.body_text())
+        })? {
+            let name = field.name().unwrap_or_default().to_owned();
+            let text = field.text().await.map_err(|e| {
+                VerificationError::bad_request("invalid request body")
+                    .with_internal_detail(e.body_text())
+            })?;
+            let parse_error = |e: serde_json::Error| {
+                VerificationError::bad_request_from(format!("invalid '{name}' part"))
+                    .with_internal_detail(format!("invalid '{name}' part: {e}"))
+            };
+            match name.as_str() {
+                "origin" => bundle.origin = text,
+                "didDocument" => {
+                    bundle.did_document = Some(serde_json::from_str(&text).map_err(parse_error)?)
+                }
+                "didDocumentMetadata" => {
+                    bundle.did_document_metadata =
+                        Some(serde_json::from_str(&text).map_err(parse_error)?);
+                }
+                "didConfiguration" => {
+                    bundle.did_configuration =
+                        Some(serde_json::from_str(&text).map_err(parse_error)?);
+                }
+                "linkedPresentations" => {
+                    bundle.linked_presentations =
+                        serde_json::from_str(&text).map_err(parse_error)?;
+                }
+                _ => {
+                    return Err(VerificationError::bad_request_from(format!(
+                        "unknown part '{name}'"
+                    )))
+                }
+            }
         }
+        Ok(bundle)
+    }
+}
+
+impl<S> FromRequest<S> for DryRunRequest
+where
+    S: Send + Sync,
+{
+    type Rejection = VerificationError;
 
-        Ok(query.0)
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_multipart = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("multipart/form-data"));
+        let bundle = if is_multipart {
+            let multipart = Multipart::from_request(req, state).await.map_err(|e| {
+                VerificationError::bad_request("invalid request body")
+                    .with_internal_detail(e.body_text())
+            })?;
+            Self::from_multipart(multipart).await?
+        } else {
+            Json::<Self>::from_request(req, state)
+                .await
+                .map_err(|e| {
+                    VerificationError::bad_request("invalid request body")
+                        .with_internal_detail(e.body_text())
+                })?
+                .0
+        };
+
+        if !bundle.origin.starts_with("https:") || !is_valid_url(&bundle.origin) {
+            return Err(VerificationError::bad_request("invalid 'origin' param"));
+        }
+
+        Ok(bundle)
     }
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[non_exhaustive]
 pub struct VerificationResultPayload {
     pub code: u32,
@@ -159,11 +970,15 @@ pub struct VerificationResultPayload {
     pub verified: bool,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[serde(tag = "result")]
 #[non_exhaustive]
 pub enum VerificationResult {
+    Blocked(VerificationResultPayload),
     DidConfigError(VerificationResultPayload),
+    DidDeactivated(VerificationResultPayload),
+    SuspectedImpersonation(VerificationResultPayload),
+    VcConsistencyError(VerificationResultPayload),
     VcParseError(VerificationResultPayload),
     VcProofError(VerificationResultPayload),
     VcProofErrorAlgorithmMismatch(VerificationResultPayload),
@@ -183,6 +998,17 @@ pub enum VerificationResult {
 }
 
 impl VerificationResult {
+    #[inline]
+    #[must_use]
+    pub fn blocked(rule: String) -> Self {
+        Self::Blocked(VerificationResultPayload {
+            message: "Blocked by an entry of the block list.".into(),
+            details: rule,
+            verified: false,
+            code: 1 << 20,
+        })
+    }
+
     #[inline]
     #[must_use]
     pub fn did_config_error(reason: String) -> Self {
@@ -194,12 +1020,147 @@ impl VerificationResult {
         })
     }
 
+    #[inline]
+    #[must_use]
+    pub fn did_deactivated(did: String) -> Self {
+        Self::DidDeactivated(VerificationResultPayload {
+            message: "DID has been deactivated.".into(),
+            details: did,
+            verified: false,
+            code: 1 << 17,
+        })
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn suspected_impersonation(e: String) -> Self {
+        Self::SuspectedImpersonation(VerificationResultPayload {
+            message: "Origin resembles another domain (suspected impersonation).".into(),
+            details: e,
+            verified: false,
+            code: 1 << 19,
+        })
+    }
+
+    /// Returns the name of the variant, e.g. `VcValid`.
+    #[inline]
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Blocked(_) => "Blocked",
+            Self::DidConfigError(_) => "DidConfigError",
+            Self::DidDeactivated(_) => "DidDeactivated",
+            Self::SuspectedImpersonation(_) => "SuspectedImpersonation",
+            Self::VcConsistencyError(_) => "VcConsistencyError",
+            Self::VcParseError(_) => "VcParseError",
+            Self::VcProofError(_) => "VcProofError",
+            Self::VcProofErrorAlgorithmMismatch(_) => "VcProofErrorAlgorithmMismatch",
+            Self::VcProofErrorKeyMismatch(_) => "VcProofErrorKeyMismatch",
+            Self::VcProofErrorMissing(_) => "VcProofErrorMissing",
+            Self::VcProofErrorSignature(_) => "VcProofErrorSignature",
+            Self::VcValid(_) => "VcValid",
+            Self::VcValidationErrorExpired(_) => "VcValidationErrorExpired",
+            Self::VcValidationErrorMissingIssuance(_) => "VcValidationErrorMissingIssuance",
+            Self::VcValidationErrorOther(_) => "VcValidationErrorOther",
+            Self::VcValidationErrorPremature(_) => "VcValidationErrorPremature",
+            Self::VcValidationErrorSubjectMismatch(_) => "VcValidationErrorSubjectMismatch",
+            Self::VpParseError(_) => "VpParseError",
+            Self::VpProofError(_) => "VpProofError",
+            Self::VpValid(_) => "VpValid",
+            Self::VpVerificationError(_) => "VpVerificationError",
+        }
+    }
+
+    /// Returns the payload of the result, regardless of the variant.
+    #[inline]
+    #[must_use]
+    pub const fn payload(&self) -> &VerificationResultPayload {
+        match self {
+            Self::Blocked(payload)
+            | Self::DidConfigError(payload)
+            | Self::DidDeactivated(payload)
+            | Self::SuspectedImpersonation(payload)
+            | Self::VcConsistencyError(payload)
+            | Self::VcParseError(payload)
+            | Self::VcProofError(payload)
+            | Self::VcProofErrorAlgorithmMismatch(payload)
+            | Self::VcProofErrorKeyMismatch(payload)
+            | Self::VcProofErrorMissing(payload)
+            | Self::VcProofErrorSignature(payload)
+            | Self::VcValid(payload)
+            | Self::VcValidationErrorExpired(payload)
+            | Self::VcValidationErrorMissingIssuance(payload)
+            | Self::VcValidationErrorOther(payload)
+            | Self::VcValidationErrorPremature(payload)
+            | Self::VcValidationErrorSubjectMismatch(payload)
+            | Self::VpParseError(payload)
+            | Self::VpProofError(payload)
+            | Self::VpValid(payload)
+            | Self::VpVerificationError(payload) => payload,
+        }
+    }
+
+    /// Suggests how to fix the cause of the result. `did` is the DID the result belongs to, if known.
+    #[inline]
+    #[must_use]
+    pub fn fix(&self, did: Option<&str>) -> String {
+        let did = did.unwrap_or("the linked DID");
+        match self {
+            Self::Blocked(_) => {
+                "Contact the operator of the verification service if the entry of the block list is wrong.".to_owned()
+            }
+            Self::DidConfigError(_) => "Reissue the domain linkage credential for the requested origin.".to_owned(),
+            Self::DidDeactivated(_) => {
+                format!("Link an active DID instead of '{did}' and publish its presentations again.")
+            }
+            Self::SuspectedImpersonation(_) => {
+                "Serve the DID configuration from the origin it was issued for, not from a similar looking domain."
+                    .to_owned()
+            }
+            Self::VcConsistencyError(_) => {
+                "Reissue the credentials so their claims agree with each other and with the origin.".to_owned()
+            }
+            Self::VcParseError(_) | Self::VpParseError(_) => {
+                "Validate the document against the W3C Verifiable Credentials Data Model.".to_owned()
+            }
+            Self::VcProofError(_)
+            | Self::VcProofErrorAlgorithmMismatch(_)
+            | Self::VcProofErrorKeyMismatch(_)
+            | Self::VcProofErrorMissing(_)
+            | Self::VcProofErrorSignature(_)
+            | Self::VpProofError(_) => {
+                "Sign the document again with a key that is listed in the issuer's DID document and don't modify it \
+                 afterwards."
+                    .to_owned()
+            }
+            Self::VcValid(_) | Self::VpValid(_) => "No fix is needed.".to_owned(),
+            Self::VcValidationErrorExpired(_) => "Reissue the credential with an expiration date in the future.".to_owned(),
+            Self::VcValidationErrorMissingIssuance(_) => "Add an issuanceDate to the credential.".to_owned(),
+            Self::VcValidationErrorOther(_) => {
+                "Correct the claims of the credential that are named in the details and reissue it.".to_owned()
+            }
+            Self::VcValidationErrorSubjectMismatch(_) => {
+                format!("Set credentialSubject.id of the credential to '{did}'.")
+            }
+            Self::VcValidationErrorPremature(_) => {
+                "Check the issuance date of the credential and the clock of the issuing system.".to_owned()
+            }
+            Self::VpVerificationError(_) => {
+                format!("Set the presentation's holder to '{did}' and sign it with a key of that DID.")
+            }
+        }
+    }
+
     /// Automatic conversion to a Result
     #[inline]
     pub const fn into_result(self) -> Result<Self, Self> {
         match self {
             Self::VcValid(_) | Self::VpValid(_) => Ok(self),
-            Self::DidConfigError(_)
+            Self::Blocked(_)
+            | Self::DidConfigError(_)
+            | Self::DidDeactivated(_)
+            | Self::SuspectedImpersonation(_)
+            | Self::VcConsistencyError(_)
             | Self::VcParseError(_)
             | Self::VcProofError(_)
             | Self::VcProofErrorAlgorithmMismatch(_)
@@ -221,7 +1182,11 @@ impl VerificationResult {
     pub fn into_vec_result(self) -> Result<Vec<Self>, Self> {
         match self {
             Self::VcValid(_) | Self::VpValid(_) => Ok(vec![self]),
-            Self::DidConfigError(_)
+            Self::Blocked(_)
+            | Self::DidConfigError(_)
+            | Self::DidDeactivated(_)
+            | Self::SuspectedImpersonation(_)
+            | Self::VcConsistencyError(_)
             | Self::VcParseError(_)
             | Self::VcProofError(_)
             | Self::VcProofErrorAlgorithmMismatch(_)
@@ -239,6 +1204,17 @@ impl VerificationResult {
         }
     }
 
+    #[inline]
+    #[must_use]
+    pub fn vc_consistency_error(e: String) -> Self {
+        Self::VcConsistencyError(VerificationResultPayload {
+            message: "Verifiable Credential contradicts other credentials or the origin.".into(),
+            details: e,
+            verified: false,
+            code: 1 << 18,
+        })
+    }
+
     #[inline]
     #[must_use]
     pub fn vc_parse_error(e: String) -> Self {
@@ -447,18 +1423,253 @@ impl From<Invalid> for VerificationResult {
     }
 }
 
-#[expect(clippy::single_call_fn, reason = "Function is used in tests")]
-fn is_valid_url(url_string: &str) -> bool {
+/// Returns `true` if the given string is a URL that can be verified, i.e. an `https:` or `did:` URL.
+#[inline]
+#[must_use]
+pub fn is_valid_url(url_string: &str) -> bool {
     Url::parse(url_string)
         .map(|url| url.scheme() == "https" || url.scheme() == "did")
         .unwrap_or(false)
 }
 
+/// Turns the query of a verification into a URL that can be verified. DIDs and HTTPS URLs are accepted as they are,
+/// bare domain names with an optional path, e.g. `www.example.com/shop`, are turned into HTTPS URLs. Internationalized
+/// domain names are converted to punycode and host names are lowercased. `None` is returned for other URLs and for
+/// malformed DIDs.
+#[inline]
+#[must_use]
+pub fn normalize_query(q: &str) -> Option<Url> {
+    let q = q.trim();
+    let url = if q.starts_with("did:") || q.contains("://") {
+        Url::parse(q).ok()?
+    } else {
+        Url::parse(&format!("https://{q}")).ok()?
+    };
+    match url.scheme() {
+        "did" if DIDBuf::from_string(url.to_string()).is_ok() => Some(url),
+        "https" if url.host().is_some() => Some(url),
+        _ => None,
+    }
+}
+
+/// Builders of verification results for tests, also used by the tests of the binary via the `test-util` feature.
+#[cfg(any(test, feature = "test-util"))]
+#[doc(hidden)]
+pub mod test_util {
+    use serde_json::Value;
+    use ssi::claims::vc::v1::SpecializedJsonCredential;
+
+    use super::{
+        CredentialVerificationDto, DidVerificationDto, GroupedVerificationResponseDto,
+        LinkedPresentationServiceDto, PresentationVerificationDto, TrustLevel, VerificationResult,
+    };
+    use crate::identity::IdentitySummary;
+    use crate::presentation::PresentationFormat;
+    use crate::resolver::{DocumentMetadata, ResolutionMetadata};
+
+    /// Returns a credential of the issuer with the given types, in addition to `VerifiableCredential`, and subject.
+    /// Its ID is derived from the issuer.
+    #[must_use]
+    pub fn credential(issuer: &str, types: &[&str], subject: Value) -> SpecializedJsonCredential {
+        let types = [&["VerifiableCredential"], types].concat();
+        serde_json::from_value(serde_json::json!({
+            "@context": "https://www.w3.org/2018/credentials/v1",
+            "id": format!("{issuer}#credential"),
+            "type": types,
+            "issuer": issuer,
+            "issuanceDate": "2025-01-01T00:00:00Z",
+            "credentialSubject": subject
+        }))
+        .unwrap()
+    }
+
+    /// Returns the verification of an active DID whose credentials, given with their results, have been served in
+    /// one valid presentation.
+    #[must_use]
+    pub fn did_verification(
+        did: &str,
+        credentials: Vec<(SpecializedJsonCredential, VerificationResult)>,
+    ) -> DidVerificationDto {
+        let presentation = PresentationVerificationDto {
+            credentials: credentials
+                .into_iter()
+                .map(|(credential, result)| CredentialVerificationDto {
+                    credential,
+                    result,
+                    warnings: Vec::new(),
+                })
+                .collect(),
+            format: PresentationFormat::Ldp,
+            presentation: serde_json::from_value(serde_json::json!({
+                "@context": ["https://www.w3.org/2018/credentials/v1"],
+                "type": ["VerifiablePresentation"],
+                "holder": did
+            }))
+            .unwrap(),
+            result: VerificationResult::vp_valid(),
+            warnings: Vec::new(),
+        };
+        DidVerificationDto {
+            document: serde_json::from_value(serde_json::json!({ "id": did })).unwrap(),
+            document_metadata: DocumentMetadata::default(),
+            resolution_metadata: ResolutionMetadata::default(),
+            result: None,
+            services: vec![LinkedPresentationServiceDto {
+                endpoint: None,
+                id: format!("{did}#linked-vp"),
+                presentations: vec![presentation],
+                result: None,
+                warnings: Vec::new(),
+            }],
+        }
+    }
+
+    /// Returns the verified verification of the origin, `None` for a DID that has been verified directly, and its
+    /// DIDs. The checks haven't been applied.
+    #[must_use]
+    pub fn grouped_dto(
+        origin: Option<&str>,
+        dids: Vec<DidVerificationDto>,
+    ) -> GroupedVerificationResponseDto {
+        GroupedVerificationResponseDto {
+            code: 0,
+            dids,
+            discovery: None,
+            identity: IdentitySummary::default(),
+            impersonation: None,
+            list_match: None,
+            origin: origin.map(str::to_owned),
+            policy: None,
+            trust_level: TrustLevel::None,
+            verified: true,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use axum::{body::Body, http::Request};
-    use core::fmt::Debug;
+
+    #[test]
+    fn query_normalization() {
+        let normalize = |q: &str| normalize_query(q).map(String::from);
+        assert_eq!(
+            normalize("example.com").as_deref(),
+            Some("https://example.com/")
+        );
+        assert_eq!(
+            normalize(" www.Example.com ").as_deref(),
+            Some("https://www.example.com/")
+        );
+        assert_eq!(
+            normalize("example.com/shop?q=1").as_deref(),
+            Some("https://example.com/shop?q=1")
+        );
+        assert_eq!(
+            normalize("example.com:3000").as_deref(),
+            Some("https://example.com:3000/")
+        );
+        assert_eq!(
+            normalize("bücher.example").as_deref(),
+            Some("https://xn--bcher-kva.example/")
+        );
+        assert_eq!(
+            normalize("https://BÜCHER.example").as_deref(),
+            Some("https://xn--bcher-kva.example/")
+        );
+        assert_eq!(
+            normalize("did:web:example.com").as_deref(),
+            Some("did:web:example.com")
+        );
+
+        assert_eq!(normalize("did:x"), None);
+        assert_eq!(normalize("did:example"), None);
+        assert_eq!(normalize("did:web:example.com#key-1"), None);
+        assert_eq!(normalize("http://example.com"), None);
+        assert_eq!(normalize("ftp://example.com"), None);
+        assert_eq!(normalize("exa mple.com"), None);
+        assert_eq!(normalize(""), None);
+    }
+
+    #[test]
+    fn test_valid_credentials() {
+        let issued = |issuer: &str, result| {
+            (
+                test_util::credential(issuer, &[], serde_json::json!({})),
+                result,
+            )
+        };
+        let mut deactivated = test_util::did_verification(
+            "did:web:b.example",
+            vec![issued(
+                "did:web:issuer.example",
+                VerificationResult::vc_valid(),
+            )],
+        );
+        deactivated.result = Some(VerificationResult::did_deactivated(
+            "did:web:b.example".into(),
+        ));
+        let dto = test_util::grouped_dto(
+            None,
+            vec![
+                test_util::did_verification(
+                    "did:web:a.example",
+                    vec![
+                        issued("did:web:issuer.example", VerificationResult::vc_valid()),
+                        issued(
+                            "did:web:issuer.example",
+                            VerificationResult::vc_validation_error_expired(String::new()),
+                        ),
+                    ],
+                ),
+                deactivated,
+                test_util::did_verification(
+                    "did:web:c.example",
+                    vec![issued("did:web:c.example", VerificationResult::vc_valid())],
+                ),
+            ],
+        );
+        let valid: Vec<_> = dto
+            .valid_credentials()
+            .map(|(index, holder, credential)| (index, holder, credential.issuer.id().as_str()))
+            .collect();
+        // the index counts the skipped credentials as well
+        assert_eq!(
+            valid,
+            [
+                (0, "did:web:a.example", "did:web:issuer.example"),
+                (3, "did:web:c.example", "did:web:c.example"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fix() {
+        let did = "did:web:example.com";
+        assert_eq!(
+            VerificationResult::vp_verification_error(String::new()).fix(Some(did)),
+            "Set the presentation's holder to 'did:web:example.com' and sign it with a key of that DID."
+        );
+        assert!(VerificationResult::did_deactivated(did.into())
+            .fix(None)
+            .contains("the linked DID"));
+        // only a mismatching subject is fixed by changing the subject
+        assert!(
+            !VerificationResult::vc_validation_error_other(String::new())
+                .fix(Some(did))
+                .contains("credentialSubject.id")
+        );
+        // results of other checks don't fall back to the hint for the DID configuration
+        let did_config = VerificationResult::did_config_error(String::new()).fix(None);
+        for result in [
+            VerificationResult::blocked(String::new()),
+            VerificationResult::suspected_impersonation(String::new()),
+            VerificationResult::vc_consistency_error(String::new()),
+        ] {
+            assert_ne!(result.fix(None), did_config, "{}", result.name());
+        }
+    }
 
     #[test]
     fn url_validation() {
@@ -474,22 +1685,89 @@ mod tests {
         assert!(!is_valid_url("http://example.com"));
     }
 
-    impl Debug for VerificationError {
-        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-            match self {
-                // _ => Ok(()),
-                Self::BadRequestJson(arg0) => f.debug_struct(&arg0.error).finish(),
-                Self::NotFoundJson(_) => f.debug_struct("VerificationError::NotFound").finish(),
-                Self::VerificationImpossible(_) => f
-                    .debug_struct("VerificationError::VerificationImpossible")
-                    .finish(),
-            }
-        }
+    #[test]
+    fn brief_response() {
+        let credential: SpecializedJsonCredential = serde_json::from_str(include_str!(
+            "../tests/credentials/credential-trust-party-issued-not-expired.json"
+        ))
+        .unwrap();
+        let dto = VerificationResponseDto {
+            code: (1 << 1) | (1 << 13),
+            credentials: vec![credential.clone(), credential],
+            discovery: None,
+            documents: Vec::new(),
+            document_metadata: Vec::new(),
+            identity: IdentitySummary::default(),
+            impersonation: None,
+            list_match: None,
+            origin: None,
+            policy: None,
+            results: vec![
+                VerificationResult::vc_valid(),
+                VerificationResult::vc_validation_error_expired(String::new()),
+            ],
+            trust_level: TrustLevel::DomainLinked,
+            verified: false,
+            warnings: Vec::new(),
+        };
+        let brief = BriefVerificationResponseDto::from(&dto);
+        assert_eq!(brief.code, (1 << 1) | (1 << 13));
+        assert_eq!(brief.trust_level, TrustLevel::DomainLinked);
+        assert!(!brief.verified);
+        assert_eq!(brief.credentials.len(), 2);
+        assert_eq!(brief.credentials[0].result, "VcValid");
+        assert_eq!(brief.credentials[1].result, "VcValidationErrorExpired");
+        assert_eq!(brief.credentials[0].types, vec!["VerifiableCredential"]);
+        assert_eq!(
+            brief.credentials[0].issuer.as_deref(),
+            Some(include_str!("../tests/dids/did-trust-party").trim())
+        );
+    }
+
+    #[test]
+    fn problem_details() {
+        let error = VerificationError::did_config_invalid(VerificationResult::did_config_error(
+            "origin mismatch".to_owned(),
+        ));
+        let problem = error.to_problem(false);
+        assert_eq!(problem.type_, ProblemType::DidConfigInvalid.uri());
+        assert_eq!(problem.status, 422);
+        assert_eq!(problem.detail.as_deref(), Some("origin mismatch"));
+        assert_eq!(problem.code, Some(1 << 16));
+        assert_eq!(problem.result.as_deref(), Some("DidConfigError"));
+
+        let problem = error.to_problem(true);
+        assert_eq!(
+            problem.detail.as_deref(),
+            Some("Verification of DID Configuration failed.")
+        );
+        assert_eq!(problem.code, Some(1 << 16));
+
+        let error = VerificationError::resolution_failure(&ResolutionError::NotFound);
+        assert_eq!(error.status, StatusCode::NOT_FOUND);
+        assert_eq!(error.problem_type, ProblemType::ResolutionFailure);
+    }
+
+    #[tokio::test]
+    async fn problem_response() {
+        let response = VerificationError::bad_request("invalid 'q' param").into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["type"], ProblemType::InvalidQuery.uri());
+        assert_eq!(problem["status"], 400);
+        assert_eq!(problem["detail"], "invalid 'q' param");
     }
 
     impl PartialEq for VerificationRequest {
         fn eq(&self, other: &Self) -> bool {
-            self.q == other.q
+            self.q == other.q && self.brief == other.brief && self.policy == other.policy
         }
     }
 
@@ -520,11 +1798,7 @@ mod tests {
         assert_eq!(
             VerificationRequest::from_request_parts(&mut parts, &())
                 .await
-                .map_err(|err| match err {
-                    VerificationError::BadRequestJson(json) => json.error,
-                    VerificationError::NotFoundJson(_) => "xyz".to_owned(),
-                    VerificationError::VerificationImpossible(_) => "abc".to_owned(),
-                })
+                .map_err(|err| err.to_problem(false).detail.unwrap())
                 .unwrap_err(),
             value
         );
@@ -535,7 +1809,29 @@ mod tests {
         check_ok(
             "http://ver.svc/verify?q=https://www.abc.com",
             VerificationRequest {
-                q: "https://www.abc.com".to_owned(),
+                q: "https://www.abc.com/".to_owned(),
+                brief: false,
+                policy: None,
+            },
+        )
+        .await;
+
+        check_ok(
+            "http://ver.svc/verify?q=https://www.abc.com&brief=true",
+            VerificationRequest {
+                q: "https://www.abc.com/".to_owned(),
+                brief: true,
+                policy: None,
+            },
+        )
+        .await;
+
+        check_ok(
+            "http://ver.svc/verify?q=https://www.abc.com&policy=marketplace",
+            VerificationRequest {
+                q: "https://www.abc.com/".to_owned(),
+                brief: false,
+                policy: Some("marketplace".to_owned()),
             },
         )
         .await;
@@ -548,6 +1844,22 @@ mod tests {
 
         check_err("http://ver.svc/verify?q=", "empty 'q' param").await;
 
-        check_err("http://ver.svc/verify?q=abc.com", "invalid 'q' param").await;
+        check_ok(
+            "http://ver.svc/verify?q=abc.com",
+            VerificationRequest {
+                q: "https://abc.com/".to_owned(),
+                brief: false,
+                policy: None,
+            },
+        )
+        .await;
+
+        check_err(
+            "http://ver.svc/verify?q=http://abc.com",
+            "invalid 'q' param",
+        )
+        .await;
+
+        check_err("http://ver.svc/verify?q=did:x", "invalid 'q' param").await;
     }
 }
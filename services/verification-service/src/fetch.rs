@@ -0,0 +1,669 @@
+use core::{net::IpAddr, time::Duration};
+use std::{collections::HashMap, sync::Arc};
+
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::{HeaderMap, CONTENT_TYPE, ETAG, LAST_MODIFIED},
+    redirect::Policy,
+    Certificate, Client, ClientBuilder, Proxy, Response, StatusCode,
+};
+use url::{Host, Url};
+
+use crate::{
+    concurrency,
+    http_cache::{CachedResponse, HttpCache},
+};
+
+/// Fetch error.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum FetchError {
+    /// The resource doesn't exist
+    #[error("Resource not found: {0}")]
+    NotFound(String),
+
+    /// The request failed or the response couldn't be read
+    #[error("Request failed: {0}")]
+    Request(String),
+
+    /// The server didn't respond in time
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+
+    /// The destination isn't allowed, e.g. a private address or a URL without HTTPS
+    #[error("Destination not allowed: {0}")]
+    Forbidden(String),
+
+    /// The response exceeds the maximum response size
+    #[error("Response too large: {0}")]
+    TooLarge(String),
+
+    /// The client configuration is invalid, e.g. a malformed proxy URL or certificate
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+}
+
+impl From<reqwest::Error> for FetchError {
+    #[inline]
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout(e.to_string())
+        } else {
+            Self::Request(e.to_string())
+        }
+    }
+}
+
+/// Limits of outbound requests.
+///
+/// The URLs that are fetched during the verification come from attacker-controlled documents. Thus, only HTTPS URLs
+/// that resolve to public addresses are fetched, unless the host is part of the allow-list.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct FetchConfig {
+    /// Timeout for establishing a connection.
+    pub connect_timeout: Duration,
+    /// Timeout for the whole request, including reading the response.
+    pub timeout: Duration,
+    /// Maximum size of a response body in bytes.
+    pub max_response_size: usize,
+    /// Maximum number of redirects that are followed.
+    pub max_redirects: usize,
+    /// Hosts that may be fetched via HTTP and may resolve to private addresses, e.g. `localhost` during development.
+    pub allow_list: Vec<String>,
+    /// User agent that identifies the service.
+    pub user_agent: String,
+    /// HTTP(S) proxy that all requests are sent through. The proxy resolves the destinations, thus it is expected to
+    /// restrict them as well.
+    pub proxy: Option<Proxy>,
+    /// Root certificates that are trusted in addition to the system's root certificates, e.g. the mkcert development
+    /// CA.
+    pub root_certificates: Vec<Certificate>,
+    /// Negotiate HTTP/2 with servers that support it. Only HTTP/1.1 is used if disabled.
+    pub http2: bool,
+    /// Maximum size of the HTTP cache in bytes, see [`HttpCache`]. The cache is disabled if zero.
+    pub cache_size: u64,
+}
+
+impl Default for FetchConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(10),
+            max_response_size: 1024 * 1024,
+            max_redirects: 5,
+            allow_list: Vec::new(),
+            user_agent: concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_owned(),
+            proxy: None,
+            root_certificates: Vec::new(),
+            http2: true,
+            cache_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+impl FetchConfig {
+    /// Sets the hosts that are exempt from the HTTPS and public address restrictions.
+    #[inline]
+    #[must_use]
+    pub fn with_allow_list(mut self, allow_list: Vec<String>) -> Self {
+        self.allow_list = allow_list;
+        self
+    }
+
+    /// Sends all requests through the given HTTP(S) proxy, e.g. `http://proxy.internal:3128`.
+    #[inline]
+    pub fn with_proxy(mut self, proxy: &str) -> Result<Self, FetchError> {
+        self.proxy = Some(Proxy::all(proxy).map_err(|e| FetchError::Config(e.to_string()))?);
+        Ok(self)
+    }
+
+    /// Trusts the certificates of the given PEM bundle in addition to the system's root certificates.
+    #[inline]
+    pub fn with_root_certificates_pem(mut self, pem: &[u8]) -> Result<Self, FetchError> {
+        let certificates =
+            Certificate::from_pem_bundle(pem).map_err(|e| FetchError::Config(e.to_string()))?;
+        self.root_certificates.extend(certificates);
+        Ok(self)
+    }
+
+    /// Returns `true` if the host is exempt from the HTTPS and public address restrictions.
+    #[inline]
+    #[must_use]
+    pub fn is_allowed_host(&self, host: &str) -> bool {
+        self.allow_list
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+
+    /// Checks that the URL may be fetched: the scheme must be HTTPS and IP addresses must be public, unless the host is
+    /// part of the allow-list. Host names are checked after DNS resolution by the client, see
+    /// [`FetchConfig::client_builder`].
+    #[inline]
+    pub fn check_url(&self, url: &Url) -> Result<(), FetchError> {
+        let host = url
+            .host()
+            .ok_or_else(|| FetchError::Forbidden(format!("{url} has no host")))?;
+        if self.is_allowed_host(&host.to_string()) {
+            return match url.scheme() {
+                "https" | "http" => Ok(()),
+                _ => Err(FetchError::Forbidden(format!("{url} is not an HTTP URL"))),
+            };
+        }
+        if url.scheme() != "https" {
+            return Err(FetchError::Forbidden(format!("{url} is not an HTTPS URL")));
+        }
+        let ip = match host {
+            Host::Ipv4(ip) => IpAddr::V4(ip),
+            Host::Ipv6(ip) => IpAddr::V6(ip),
+            Host::Domain(_) => return Ok(()),
+        };
+        if is_public_ip(ip) {
+            Ok(())
+        } else {
+            Err(FetchError::Forbidden(format!(
+                "{url} is not a public address"
+            )))
+        }
+    }
+
+    /// Returns a client builder with the configured timeouts, user agent, proxy, root certificates and a DNS resolver
+    /// that only returns public addresses. Redirects are checked and limited as well.
+    #[inline]
+    pub fn client_builder(&self) -> ClientBuilder {
+        let config = self.clone();
+        let redirect = Policy::custom(move |attempt| {
+            if attempt.previous().len() > config.max_redirects {
+                attempt.error(format!("more than {} redirects", config.max_redirects))
+            } else if let Err(e) = config.check_url(attempt.url()) {
+                attempt.error(e)
+            } else {
+                attempt.follow()
+            }
+        });
+        let mut builder = Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout)
+            .user_agent(self.user_agent.clone())
+            .redirect(redirect)
+            .dns_resolver(Arc::new(PublicResolver {
+                allow_list: self.allow_list.clone(),
+            }));
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        if !self.http2 {
+            builder = builder.http1_only();
+        }
+        builder
+    }
+
+    /// Reads the response body, fails if it exceeds the maximum response size.
+    #[inline]
+    pub async fn read_body(&self, mut response: Response) -> Result<String, FetchError> {
+        let url = response.url().clone();
+        let too_large =
+            || FetchError::TooLarge(format!("{url} exceeds {} bytes", self.max_response_size));
+        if response
+            .content_length()
+            .is_some_and(|length| length > self.max_response_size as u64)
+        {
+            return Err(too_large());
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > self.max_response_size {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+/// Returns `true` if the address is publicly routable, i.e. not a loopback, private, link-local, unspecified or
+/// otherwise reserved address.
+#[inline]
+#[must_use]
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // shared address space, RFC 6598
+                || (a == 100 && (b & 0b1100_0000) == 64)
+                // "this network" and reserved for future use
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // unique local, RFC 4193
+                || (first & 0xfe00) == 0xfc00
+                // link-local
+                || (first & 0xffc0) == 0xfe80
+                // documentation, RFC 3849
+                || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+        }
+    }
+}
+
+/// DNS resolver that drops all addresses that aren't public, so host names can't be used to reach internal services.
+struct PublicResolver {
+    allow_list: Vec<String>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+        let allowed = self
+            .allow_list
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(&host));
+        Box::pin(async move {
+            let addrs: Vec<_> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| allowed || is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(FetchError::Forbidden(format!("{host} has no public address")).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Document that has been retrieved by the [`Fetcher`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Document {
+    pub body: String,
+    /// Media type of the body as declared by the server, e.g. `application/json`. Absent for documents that are served
+    /// from memory.
+    pub content_type: Option<String>,
+    /// Entity tag of the HTTP response, i.e. the version of the document. Absent for documents that are served from
+    /// memory.
+    pub etag: Option<String>,
+    /// `Last-Modified` header of the HTTP response, e.g. `Wed, 01 Jan 2025 00:00:00 GMT`. Absent for documents that
+    /// are served from memory.
+    pub last_modified: Option<String>,
+}
+
+impl Document {
+    /// Creates a document from the body and the headers of its HTTP response.
+    fn from_response(body: String, headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        Self {
+            body,
+            content_type: header(CONTENT_TYPE),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    /// Creates a document from a response that has been stored by the [`HttpCache`].
+    fn from_cached(response: &CachedResponse) -> Self {
+        Self {
+            body: response.body.clone(),
+            content_type: response.content_type.clone(),
+            etag: response.etag.clone(),
+            last_modified: response.last_modified.clone(),
+        }
+    }
+}
+
+/// Retrieves the documents that are referenced during the verification, e.g. the well-known DID configuration and
+/// Linked Verifiable Presentations.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum Fetcher {
+    /// Downloads documents via HTTP within the limits of the configuration. Responses are cached as permitted by their
+    /// headers.
+    Http(Client, Arc<FetchConfig>, HttpCache),
+    /// Serves documents from memory, e.g. artifacts that haven't been published yet. Unknown URLs result in
+    /// [`FetchError::NotFound`].
+    Memory(Arc<HashMap<Url, String>>),
+}
+
+impl Default for Fetcher {
+    #[inline]
+    fn default() -> Self {
+        Self::new(FetchConfig::default()).expect("HTTP client can be initialized")
+    }
+}
+
+impl Fetcher {
+    /// Creates a fetcher that downloads documents via HTTP. The client, its connection pool and the HTTP cache are
+    /// shared by all clones of the fetcher.
+    #[inline]
+    pub fn new(config: FetchConfig) -> Result<Self, FetchError> {
+        let client = config.client_builder().build()?;
+        let cache = HttpCache::new(config.cache_size);
+        Ok(Self::Http(client, Arc::new(config), cache))
+    }
+
+    /// Stores HTTP responses in the given cache instead of the in-memory cache of the configuration. Fetchers that
+    /// serve documents from memory aren't affected.
+    #[inline]
+    #[must_use]
+    pub fn with_cache(self, cache: HttpCache) -> Self {
+        match self {
+            Self::Http(client, config, _) => Self::Http(client, config, cache),
+            Self::Memory(documents) => Self::Memory(documents),
+        }
+    }
+
+    /// Creates a fetcher that serves the given documents from memory.
+    #[inline]
+    #[must_use]
+    pub fn from_documents(documents: HashMap<Url, String>) -> Self {
+        Self::Memory(Arc::new(documents))
+    }
+
+    /// Retrieves the body of the given URL.
+    #[inline]
+    pub async fn fetch(&self, url: &Url) -> Result<String, FetchError> {
+        self.fetch_document(url).await.map(|document| document.body)
+    }
+
+    /// Retrieves the body of the given URL together with its media type. Responses with a status other than 2xx are
+    /// errors, `304 Not Modified` is answered from the cache.
+    #[inline]
+    pub async fn fetch_document(&self, url: &Url) -> Result<Document, FetchError> {
+        match self {
+            Self::Http(client, config, cache) => {
+                config.check_url(url)?;
+                let stored = cache.get(url).await;
+                if let Some(response) = stored.as_ref().filter(|response| response.is_fresh()) {
+                    return Ok(Document::from_cached(response));
+                }
+                let _permit = concurrency::acquire_fetch().await;
+                let mut request = client.get(url.clone());
+                if let Some(response) = &stored {
+                    request = response.conditional(request);
+                }
+                let response = request.send().await?;
+                match (response.status(), stored) {
+                    (StatusCode::NOT_FOUND, _) => {
+                        cache.invalidate(url).await;
+                        Err(FetchError::NotFound(url.to_string()))
+                    }
+                    (StatusCode::NOT_MODIFIED, Some(stored)) => {
+                        // validators of the 304 response replace the stored ones
+                        let updated = Document::from_response(String::new(), response.headers());
+                        let document = Document::from_cached(&stored);
+                        Ok(Document {
+                            body: cache.revalidated(url, stored, response.headers()).await,
+                            etag: updated.etag.or(document.etag),
+                            last_modified: updated.last_modified.or(document.last_modified),
+                            ..document
+                        })
+                    }
+                    // error pages must not be parsed as documents
+                    (status, _) if !status.is_success() => {
+                        Err(FetchError::Request(format!("{url}: {status}")))
+                    }
+                    (status, _) => {
+                        let headers = response.headers().clone();
+                        let body = config.read_body(response).await?;
+                        if status == StatusCode::OK {
+                            cache.store(url, &headers, &body).await;
+                        }
+                        Ok(Document::from_response(body, &headers))
+                    }
+                }
+            }
+            Self::Memory(documents) => documents
+                .get(url)
+                .map(|body| Document {
+                    body: body.clone(),
+                    content_type: None,
+                    etag: None,
+                    last_modified: None,
+                })
+                .ok_or_else(|| FetchError::NotFound(url.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{io::AsyncWriteExt as _, net::TcpListener};
+
+    /// Serves the given raw HTTP response once on a local port and returns the URL of the server.
+    async fn serve_once(response: Vec<u8>) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.write_all(&response).await;
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+        Url::parse(&format!("http://127.0.0.1:{port}/vp.json")).unwrap()
+    }
+
+    fn local_config() -> FetchConfig {
+        FetchConfig::default().with_allow_list(vec!["127.0.0.1".to_owned()])
+    }
+
+    #[tokio::test]
+    async fn fetch_from_memory() {
+        let url = Url::parse("https://example.com/vp.json").unwrap();
+        let fetcher = Fetcher::from_documents(HashMap::from([(url.clone(), "{}".to_owned())]));
+
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "{}");
+        assert!(matches!(
+            fetcher
+                .fetch(&Url::parse("https://example.com/other.json").unwrap())
+                .await,
+            Err(FetchError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn public_ip() {
+        for ip in ["1.1.1.1", "2606:4700:4700::1111", "::ffff:1.1.1.1"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fc00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn check_url() {
+        let config = FetchConfig::default();
+        let check = |url: &str| config.check_url(&Url::parse(url).unwrap());
+        assert!(check("https://example.com/vp.json").is_ok());
+        assert!(check("https://1.1.1.1/vp.json").is_ok());
+        assert!(matches!(
+            check("http://example.com/vp.json"),
+            Err(FetchError::Forbidden(_))
+        ));
+        assert!(matches!(
+            check("https://169.254.169.254/latest/meta-data"),
+            Err(FetchError::Forbidden(_))
+        ));
+        assert!(matches!(
+            check("https://[::1]/vp.json"),
+            Err(FetchError::Forbidden(_))
+        ));
+
+        let config = FetchConfig::default().with_allow_list(vec!["localhost".to_owned()]);
+        assert!(config
+            .check_url(&Url::parse("http://localhost:3000/vp.json").unwrap())
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn reject_private_address_after_resolution() {
+        let fetcher = Fetcher::new(FetchConfig::default()).unwrap();
+        assert!(matches!(
+            fetcher
+                .fetch(&Url::parse("https://localhost/vp.json").unwrap())
+                .await,
+            Err(FetchError::Request(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn fetch_from_allowed_host() {
+        let url = serve_once(
+            b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}".to_vec(),
+        )
+        .await;
+        let fetcher = Fetcher::new(local_config()).unwrap();
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "{}");
+    }
+
+    #[tokio::test]
+    async fn fetch_error_status() {
+        let fetcher = Fetcher::new(local_config()).unwrap();
+        for (response, not_found) in [
+            ("HTTP/1.1 404 Not Found", true),
+            ("HTTP/1.1 403 Forbidden", false),
+            ("HTTP/1.1 500 Internal Server Error", false),
+        ] {
+            let url = serve_once(
+                format!("{response}\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{{}}")
+                    .into_bytes(),
+            )
+            .await;
+            match fetcher.fetch_document(&url).await {
+                Err(FetchError::NotFound(_)) => assert!(not_found, "{response}"),
+                Err(FetchError::Request(e)) => {
+                    assert!(!not_found && e.contains(&response[9..]), "{e}");
+                }
+                _ => panic!("{response} has been accepted"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_content_type() {
+        let url = serve_once(
+            b"HTTP/1.1 200 OK\r\ncontent-type: application/vp+jwt\r\netag: \"v2\"\r\nlast-modified: Wed, 01 Jan 2025 00:00:00 GMT\r\ncontent-length: 5\r\nconnection: close\r\n\r\na.b.c"
+                .to_vec(),
+        )
+        .await;
+        let fetcher = Fetcher::new(local_config()).unwrap();
+        let document = fetcher.fetch_document(&url).await.unwrap();
+        assert_eq!(document.body, "a.b.c");
+        assert_eq!(document.content_type.as_deref(), Some("application/vp+jwt"));
+        assert_eq!(document.etag.as_deref(), Some("\"v2\""));
+        assert_eq!(
+            document.last_modified.as_deref(),
+            Some("Wed, 01 Jan 2025 00:00:00 GMT")
+        );
+    }
+
+    #[tokio::test]
+    async fn send_user_agent() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let request = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = vec![0; 4096];
+            let read = tokio::io::AsyncReadExt::read(&mut stream, &mut buffer)
+                .await
+                .unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buffer[..read]).to_lowercase()
+        });
+        let mut config = local_config();
+        config.user_agent = "test-agent/1.0".to_owned();
+        let fetcher = Fetcher::new(config).unwrap();
+        let url = Url::parse(&format!("http://127.0.0.1:{port}/")).unwrap();
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "{}");
+        assert!(request
+            .await
+            .unwrap()
+            .contains("user-agent: test-agent/1.0"));
+    }
+
+    #[test]
+    fn configuration() {
+        let config = FetchConfig::default()
+            .with_root_certificates_pem(include_bytes!("../tests/certificates/development-ca.pem"))
+            .unwrap();
+        assert_eq!(config.root_certificates.len(), 1);
+        assert!(config.client_builder().build().is_ok());
+
+        assert!(matches!(
+            FetchConfig::default().with_proxy("not a url"),
+            Err(FetchError::Config(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reject_large_response() {
+        let body = "x".repeat(64);
+        let url = serve_once(
+            format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .into_bytes(),
+        )
+        .await;
+        let mut config = local_config();
+        config.max_response_size = 16;
+        let fetcher = Fetcher::new(config).unwrap();
+        assert!(matches!(
+            fetcher.fetch(&url).await,
+            Err(FetchError::TooLarge(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn timeout_slow_response() {
+        // headers are never completed
+        let url = serve_once(b"HTTP/1.1 200 OK\r\n".to_vec()).await;
+        let mut config = local_config();
+        config.timeout = Duration::from_millis(200);
+        let fetcher = Fetcher::new(config).unwrap();
+        assert!(matches!(
+            fetcher.fetch(&url).await,
+            Err(FetchError::Timeout(_))
+        ));
+    }
+}
@@ -0,0 +1,343 @@
+use core::time::Duration;
+use std::{sync::Arc, time::SystemTime};
+
+use reqwest::{
+    header::{
+        HeaderMap, HeaderValue, AGE, CACHE_CONTROL, CONTENT_TYPE, DATE, ETAG, EXPIRES,
+        IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    },
+    RequestBuilder,
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::cache::{CacheBackend, MemoryBackend};
+
+/// Time a stale response with validators is kept for revalidation.
+const REVALIDATION_TIME: Duration = Duration::from_secs(60 * 60);
+
+/// Response that has been stored by the [`HttpCache`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedResponse {
+    /// Response body.
+    pub body: String,
+    /// Media type of the body, e.g. `application/json`.
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// Entity tag of the response, sent as `If-None-Match` to revalidate it.
+    pub etag: Option<String>,
+    /// Date the resource has been modified last, sent as `If-Modified-Since` to revalidate it.
+    pub last_modified: Option<String>,
+    fresh_until: SystemTime,
+}
+
+impl CachedResponse {
+    /// Returns `true` if the response may be used without contacting the server.
+    #[inline]
+    #[must_use]
+    pub fn is_fresh(&self) -> bool {
+        SystemTime::now() < self.fresh_until
+    }
+
+    /// Turns the request into a conditional request that is answered with `304 Not Modified` if the stored response
+    /// is still valid.
+    #[inline]
+    pub fn conditional(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+
+    /// Returns `true` if the response can be revalidated with a conditional request.
+    fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+/// HTTP cache of fetched documents, see <https://www.rfc-editor.org/rfc/rfc9111>.
+///
+/// Fresh responses are served without contacting the server. Stale responses are revalidated with a conditional
+/// request if the server sent an `ETag` or `Last-Modified` header. Freshness is derived from the `Cache-Control` and
+/// `Expires` headers, responses without explicit freshness are revalidated on every use. Failures of the backend are
+/// treated like missing responses.
+#[derive(Clone, Debug)]
+pub struct HttpCache {
+    backend: Option<Arc<dyn CacheBackend>>,
+}
+
+impl HttpCache {
+    /// Creates a cache that stores up to `max_size` bytes in memory. The cache is disabled if zero.
+    #[inline]
+    #[must_use]
+    pub fn new(max_size: u64) -> Self {
+        let backend =
+            (max_size > 0).then(|| Arc::new(MemoryBackend::new(max_size)) as Arc<dyn CacheBackend>);
+        Self { backend }
+    }
+
+    /// Creates a cache that stores responses in the given backend, e.g. to share them with other replicas.
+    #[inline]
+    #[must_use]
+    pub fn with_backend(backend: Arc<dyn CacheBackend>) -> Self {
+        Self {
+            backend: Some(backend),
+        }
+    }
+
+    /// Returns the stored response of the URL, it might be stale.
+    #[inline]
+    pub async fn get(&self, url: &Url) -> Option<CachedResponse> {
+        let value = self.backend.as_ref()?.get(&key(url)).await.ok()??;
+        serde_json::from_slice(&value).ok()
+    }
+
+    /// Stores the response of the URL if the headers permit it. Responses that are neither fresh nor can be
+    /// revalidated are dropped.
+    #[inline]
+    pub async fn store(&self, url: &Url, headers: &HeaderMap, body: &str) {
+        let Some(backend) = &self.backend else {
+            return;
+        };
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let Some(freshness) = freshness(headers, SystemTime::now()) else {
+            let _ = backend.remove(&key(url)).await;
+            return;
+        };
+        let response = CachedResponse {
+            body: body.to_owned(),
+            content_type: header(CONTENT_TYPE),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            fresh_until: SystemTime::now() + freshness,
+        };
+        let ttl = if response.has_validators() {
+            freshness + REVALIDATION_TIME
+        } else {
+            freshness
+        };
+        if let Ok(value) = serde_json::to_vec(&response) {
+            let _ = backend.set(&key(url), value, ttl).await;
+        }
+    }
+
+    /// Refreshes the stored response after the server confirmed it with `304 Not Modified` and returns its body.
+    #[inline]
+    pub async fn revalidated(
+        &self,
+        url: &Url,
+        stored: CachedResponse,
+        headers: &HeaderMap,
+    ) -> String {
+        let mut merged = HeaderMap::new();
+        let stored_headers = [
+            (CONTENT_TYPE, &stored.content_type),
+            (ETAG, &stored.etag),
+            (LAST_MODIFIED, &stored.last_modified),
+        ];
+        for (name, value) in stored_headers {
+            if let Some(value) = value
+                .as_deref()
+                .and_then(|value| HeaderValue::from_str(value).ok())
+            {
+                merged.insert(name, value);
+            }
+        }
+        // headers of the 304 response update the stored ones
+        for (name, value) in headers {
+            merged.insert(name, value.clone());
+        }
+        self.store(url, &merged, &stored.body).await;
+        stored.body
+    }
+
+    /// Removes the stored response of the URL, e.g. after the resource has been deleted.
+    #[inline]
+    pub async fn invalidate(&self, url: &Url) {
+        if let Some(backend) = &self.backend {
+            let _ = backend.remove(&key(url)).await;
+        }
+    }
+}
+
+/// Returns the key of the URL's response in the backend.
+fn key(url: &Url) -> String {
+    format!("http:{url}")
+}
+
+/// Returns the time the response is fresh, see <https://www.rfc-editor.org/rfc/rfc9111#section-4.2>. `None` is
+/// returned if the response must not be stored.
+fn freshness(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let directives: Vec<String> = headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.trim().to_ascii_lowercase())
+        .collect();
+    if directives.iter().any(|directive| directive == "no-store") {
+        return None;
+    }
+    if directives.iter().any(|directive| directive == "no-cache") {
+        return Some(Duration::ZERO);
+    }
+    let header_date = |name| {
+        headers
+            .get(name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+            .and_then(|value| httpdate::parse_http_date(value).ok())
+    };
+    let lifetime = directives
+        .iter()
+        .find_map(|directive| directive.strip_prefix("max-age="))
+        .map(|seconds| Duration::from_secs(seconds.trim_matches('"').parse().unwrap_or(0)))
+        .or_else(|| {
+            // invalid dates, e.g. `Expires: 0`, represent a time in the past
+            let expires = header_date(EXPIRES).unwrap_or(SystemTime::UNIX_EPOCH);
+            let date = header_date(DATE).unwrap_or(now);
+            headers
+                .contains_key(EXPIRES)
+                .then(|| expires.duration_since(date).unwrap_or_default())
+        })
+        .unwrap_or_default();
+    let age = headers
+        .get(AGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .map_or(Duration::ZERO, Duration::from_secs);
+    Some(lifetime.saturating_sub(age))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tokio::{
+        io::{AsyncReadExt as _, AsyncWriteExt as _},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::fetch::{FetchConfig, Fetcher};
+
+    fn headers(values: &[(&'static str, &str)]) -> HeaderMap {
+        values
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_freshness() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let freshness = |values: &[(&'static str, &str)]| freshness(&headers(values), now);
+
+        assert_eq!(freshness(&[]), Some(Duration::ZERO));
+        assert_eq!(
+            freshness(&[("cache-control", "public, max-age=300")]),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            freshness(&[("cache-control", "max-age=300"), ("age", "100")]),
+            Some(Duration::from_secs(200))
+        );
+        assert_eq!(
+            freshness(&[("cache-control", "no-store, max-age=300")]),
+            None
+        );
+        assert_eq!(
+            freshness(&[("cache-control", "no-cache, max-age=300")]),
+            Some(Duration::ZERO)
+        );
+        // max-age takes precedence over Expires
+        let date = httpdate::fmt_http_date(now);
+        let expires = httpdate::fmt_http_date(now + Duration::from_secs(60));
+        assert_eq!(
+            freshness(&[("date", &date), ("expires", &expires)]),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            freshness(&[("cache-control", "max-age=10"), ("expires", &expires)]),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(freshness(&[("expires", "0")]), Some(Duration::ZERO));
+    }
+
+    /// Serves the given responses, one per connection, and records the received requests.
+    async fn serve(responses: Vec<&'static str>) -> (Url, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&requests);
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let length = stream.read(&mut request).await.unwrap();
+                received
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(&request[..length]).to_lowercase());
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (
+            Url::parse(&format!("http://127.0.0.1:{port}/did.json")).unwrap(),
+            requests,
+        )
+    }
+
+    fn fetcher() -> Fetcher {
+        Fetcher::new(FetchConfig::default().with_allow_list(vec!["127.0.0.1".to_owned()])).unwrap()
+    }
+
+    #[tokio::test]
+    async fn serve_fresh_response_from_cache() {
+        let (url, requests) = serve(vec![
+            "HTTP/1.1 200 OK\r\ncache-control: max-age=60\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}",
+        ])
+        .await;
+        let fetcher = fetcher();
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "{}");
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "{}");
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn revalidate_stale_response() {
+        let (url, requests) = serve(vec![
+            "HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}",
+            "HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\nconnection: close\r\n\r\n",
+        ])
+        .await;
+        let fetcher = fetcher();
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "{}");
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "{}");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[0].contains("if-none-match"));
+        assert!(requests[1].contains("if-none-match: \"v1\""));
+    }
+
+    #[tokio::test]
+    async fn skip_responses_without_validators() {
+        let (url, requests) = serve(vec![
+            "HTTP/1.1 200 OK\r\ncache-control: no-store, max-age=60\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}",
+            "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n[]",
+        ])
+        .await;
+        let fetcher = fetcher();
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "{}");
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "[]");
+        let requests = requests.lock().unwrap();
+        assert!(!requests[1].contains("if-none-match"));
+    }
+}
@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ssi::claims::vc::v1::SpecializedJsonCredential;
+use utoipa::ToSchema;
+
+use crate::dto::GroupedVerificationResponseDto;
+
+/// Properties of a credential subject that contain the legal name, in the order of their precedence.
+const LEGAL_NAME: [&str; 2] = ["legalName", "name"];
+/// Properties that contain the Legal Entity Identifier, e.g. schema.org `leiCode` or `LEI` of GLEIF credentials.
+const LEI: [&str; 4] = ["leiCode", "lei", "LEI", "legalEntityIdentifier"];
+/// Properties that contain the company registration, e.g. the European Unique Identifier of the EU business registers.
+const REGISTRATION_NUMBER: [&str; 5] = [
+    "EUID",
+    "euid",
+    "registrationNumber",
+    "companyRegistrationNumber",
+    "legalPersonIdentifier",
+];
+/// Properties that contain the VAT identification number.
+const VAT_ID: [&str; 3] = ["vatID", "vatId", "vatNumber"];
+/// Properties that contain the postal address, e.g. schema.org `address` or Gaia-X `legalAddress`.
+const ADDRESS: [&str; 4] = [
+    "address",
+    "legalAddress",
+    "headquarterAddress",
+    "registeredAddress",
+];
+/// Properties that contain the contact email address, directly or in a schema.org `contactPoint`.
+const EMAIL: [&str; 1] = ["email"];
+
+/// Operator of the verified URL or DID, extracted from the claims of credentials that have been verified
+/// successfully.
+///
+/// Credentials of third parties take precedence over self-issued credentials, otherwise the first credential that
+/// contains a property wins. Every property references its source credential.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct IdentitySummary {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<AddressClaim>,
+    /// Contact email address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<IdentityClaim>,
+    /// Legal name of the organization, e.g. schema.org `legalName` or `name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legal_name: Option<IdentityClaim>,
+    /// Legal Entity Identifier, see <https://www.gleif.org/>.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lei: Option<IdentityClaim>,
+    /// Company registration, e.g. the European Unique Identifier (EUID).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_number: Option<IdentityClaim>,
+    /// VAT identification number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vat_id: Option<IdentityClaim>,
+}
+
+/// Value of a property of the [`IdentitySummary`] and the credential that contains it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+#[non_exhaustive]
+pub struct IdentityClaim {
+    pub source: CredentialReference,
+    pub value: String,
+}
+
+/// Postal address of the [`IdentitySummary`] and the credential that contains it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+#[non_exhaustive]
+pub struct AddressClaim {
+    pub source: CredentialReference,
+    pub value: PostalAddress,
+}
+
+/// Postal address, modeled after schema.org `PostalAddress`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct PostalAddress {
+    /// Country, e.g. `DE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locality: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postal_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub street_address: Option<String>,
+}
+
+/// Credential a claim has been extracted from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+#[non_exhaustive]
+pub struct CredentialReference {
+    /// Index of the credential in `credentials` of the verification result, i.e. in the order of the grouped result.
+    pub credential: usize,
+    /// ID of the credential, if present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// DID of the issuer.
+    pub issuer: String,
+}
+
+impl IdentitySummary {
+    /// Extracts the identity of the operator from the credentials of the verification that are valid.
+    #[inline]
+    #[must_use]
+    pub fn from_verification(dto: &GroupedVerificationResponseDto) -> Self {
+        Self::from_credentials(dto.valid_credentials())
+    }
+
+    /// Extracts the identity from credentials, given with their index and the DID of their holder.
+    pub(crate) fn from_credentials<'a>(
+        credentials: impl Iterator<Item = (usize, &'a str, &'a SpecializedJsonCredential)>,
+    ) -> Self {
+        let mut credentials: Vec<_> = credentials.collect();
+        // the sort is stable, thus credentials of the same kind keep their order
+        credentials
+            .sort_by_key(|(_, holder, credential)| credential.issuer.id().as_str() == *holder);
+
+        let mut summary = Self::default();
+        for (index, _, credential) in credentials {
+            let json = serde_json::to_value(credential).unwrap_or_default();
+            let source = CredentialReference {
+                credential: index,
+                id: credential.id.as_ref().map(ToString::to_string),
+                issuer: credential.issuer.id().to_string(),
+            };
+            for subject in credential_subjects(&json) {
+                summary.extract(subject, &source);
+            }
+        }
+        summary
+    }
+
+    /// Fills the properties that are still missing with the claims of the subject.
+    fn extract(&mut self, subject: &Value, source: &CredentialReference) {
+        let claim = |keys: &[&str]| {
+            text(subject, keys).map(|value| IdentityClaim {
+                source: source.clone(),
+                value,
+            })
+        };
+        self.legal_name = self.legal_name.take().or_else(|| claim(&LEGAL_NAME));
+        self.lei = self.lei.take().or_else(|| claim(&LEI));
+        self.registration_number = self
+            .registration_number
+            .take()
+            .or_else(|| claim(&REGISTRATION_NUMBER));
+        self.vat_id = self.vat_id.take().or_else(|| claim(&VAT_ID));
+        self.email = self.email.take().or_else(|| {
+            let contact_points =
+                property(subject, &["contactPoint"])
+                    .into_iter()
+                    .flat_map(|contact_point| match contact_point {
+                        Value::Array(contact_points) => contact_points.iter().collect(),
+                        contact_point => vec![contact_point],
+                    });
+            core::iter::once(subject)
+                .chain(contact_points)
+                .find_map(|value| text(value, &EMAIL))
+                .map(|email| IdentityClaim {
+                    source: source.clone(),
+                    value: email
+                        .strip_prefix("mailto:")
+                        .map_or_else(|| email.clone(), ToOwned::to_owned),
+                })
+        });
+        self.address = self.address.take().or_else(|| {
+            property(subject, &ADDRESS)
+                .and_then(postal_address)
+                .map(|value| AddressClaim {
+                    source: source.clone(),
+                    value,
+                })
+        });
+    }
+
+    /// Returns `true` if no property has been extracted.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Returns the subjects of the credential in its JSON representation.
+pub(crate) fn credential_subjects(credential: &Value) -> Vec<&Value> {
+    match credential.get("credentialSubject") {
+        Some(Value::Array(subjects)) => subjects.iter().collect(),
+        Some(subject) => vec![subject],
+        None => Vec::new(),
+    }
+}
+
+/// Returns the first of the given properties of the object. Prefixes of compact IRIs are ignored, e.g. `gx:vatID`
+/// matches `vatID`.
+pub(crate) fn property<'a>(object: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    let object = object.as_object()?;
+    keys.iter().find_map(|key| {
+        object.iter().find_map(|(name, value)| {
+            let local_name = name
+                .rsplit_once(':')
+                .map_or(name.as_str(), |(_, local)| local);
+            (local_name == *key).then_some(value)
+        })
+    })
+}
+
+/// Returns the first of the given properties of the object as text. JSON-LD value objects and numbers are converted,
+/// empty values are ignored.
+fn text(object: &Value, keys: &[&str]) -> Option<String> {
+    let value = property(object, keys)?;
+    let value = match value {
+        Value::Object(_) => property(value, &["@value", "name"])?,
+        value => value,
+    };
+    let text = match value {
+        Value::String(text) => text.trim().to_owned(),
+        Value::Number(number) => number.to_string(),
+        Value::Null | Value::Bool(_) | Value::Array(_) | Value::Object(_) => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// Converts a schema.org `PostalAddress`, a vCard address or an unstructured address into a postal address.
+fn postal_address(value: &Value) -> Option<PostalAddress> {
+    let address = match value {
+        Value::String(address) if !address.trim().is_empty() => PostalAddress {
+            street_address: Some(address.trim().to_owned()),
+            ..PostalAddress::default()
+        },
+        Value::Object(_) => PostalAddress {
+            country: text(
+                value,
+                &[
+                    "addressCountry",
+                    "countryCode",
+                    "countryName",
+                    "country-name",
+                ],
+            ),
+            locality: text(value, &["addressLocality", "locality"]),
+            postal_code: text(value, &["postalCode", "postal-code"]),
+            region: text(
+                value,
+                &["addressRegion", "region", "countrySubdivisionCode"],
+            ),
+            street_address: text(value, &["streetAddress", "street-address"]),
+        },
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Array(_) => {
+            return None
+        }
+    };
+    (address != PostalAddress::default()).then_some(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::test_util::credential;
+
+    #[test]
+    fn test_from_credentials() {
+        let holder = "did:example:shop";
+        let self_issued = credential(
+            "did:example:shop",
+            &[],
+            serde_json::json!({
+                "id": holder,
+                "name": "Shop",
+                "email": "mailto:info@shop.example",
+                "address": {
+                    "type": "PostalAddress",
+                    "streetAddress": "Hauptstraße 1",
+                    "postalCode": "10115",
+                    "addressLocality": "Berlin",
+                    "addressCountry": {"type": "Country", "name": "DE"}
+                }
+            }),
+        );
+        let registration = credential(
+            "did:example:registry",
+            &[],
+            serde_json::json!({
+                "id": holder,
+                "gx:legalName": "Shop GmbH",
+                "gx:EUID": "DEB1101R.HRB123456",
+                "gx:vatID": "DE123456789",
+                "gx:leiCode": "5299000J2N45DDNE4Y28"
+            }),
+        );
+        let summary = IdentitySummary::from_credentials(
+            [(0, holder, &self_issued), (1, holder, &registration)].into_iter(),
+        );
+
+        // the third party takes precedence
+        let legal_name = summary.legal_name.unwrap();
+        assert_eq!(legal_name.value, "Shop GmbH");
+        assert_eq!(legal_name.source.credential, 1);
+        assert_eq!(legal_name.source.issuer, "did:example:registry");
+        assert_eq!(
+            legal_name.source.id.as_deref(),
+            Some("did:example:registry#credential")
+        );
+        assert_eq!(
+            summary.registration_number.unwrap().value,
+            "DEB1101R.HRB123456"
+        );
+        assert_eq!(summary.vat_id.unwrap().value, "DE123456789");
+        assert_eq!(summary.lei.unwrap().value, "5299000J2N45DDNE4Y28");
+
+        // missing properties are filled with self-issued claims
+        let email = summary.email.unwrap();
+        assert_eq!(email.value, "info@shop.example");
+        assert_eq!(email.source.credential, 0);
+        let address = summary.address.unwrap().value;
+        assert_eq!(address.street_address.as_deref(), Some("Hauptstraße 1"));
+        assert_eq!(address.postal_code.as_deref(), Some("10115"));
+        assert_eq!(address.locality.as_deref(), Some("Berlin"));
+        assert_eq!(address.country.as_deref(), Some("DE"));
+        assert_eq!(address.region, None);
+    }
+
+    #[test]
+    fn test_contact_point() {
+        let subject = serde_json::json!({
+            "contactPoint": [{"contactType": "customer service", "email": "support@shop.example"}]
+        });
+        let summary = IdentitySummary::from_credentials(
+            [(
+                0,
+                "did:example:shop",
+                &credential("did:example:registry", &[], subject),
+            )]
+            .into_iter(),
+        );
+        assert_eq!(summary.email.unwrap().value, "support@shop.example");
+        assert!(summary.legal_name.is_none());
+
+        let summary = IdentitySummary::from_credentials(
+            [(
+                0,
+                "did:example:shop",
+                &credential("did:example:registry", &[], serde_json::json!({})),
+            )]
+            .into_iter(),
+        );
+        assert!(summary.is_empty());
+    }
+}
@@ -0,0 +1,417 @@
+use serde::{Deserialize, Serialize};
+use unicode_security::{is_potential_mixed_script_confusable_char, skeleton, MixedScript as _};
+use url::Url;
+use utoipa::ToSchema;
+
+use crate::consistency::{claimed_domains, covers};
+use crate::dto::{GroupedVerificationResponseDto, VerificationResult};
+
+/// Default number of edits that turn a domain name into a typosquat of another, e.g. `paypa.com` for `paypal.com`.
+pub const DEFAULT_MAX_DISTANCE: usize = 1;
+
+/// Second-level labels that are part of the public suffix below country code TLDs, e.g. `co.uk`.
+const SECOND_LEVEL_SUFFIXES: [&str; 10] = [
+    "ac", "co", "com", "edu", "go", "gov", "ne", "net", "or", "org",
+];
+
+/// Names shorter than this are too ambiguous to be reported as typosquats.
+const MIN_TYPOSQUAT_LENGTH: usize = 4;
+
+/// Technique that makes the verified origin resemble another domain.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum ImpersonationKind {
+    /// Confusable characters, e.g. Cyrillic `а` in `pаypal.com` or `rn` in `rnicrosoft.com`.
+    Homoglyph,
+    /// Labels that mix scripts, e.g. Latin and Cyrillic, without resembling a known domain.
+    MixedScript,
+    /// The domain appears in the subdomain of another domain, e.g. `paypal.com.login.example`.
+    Subdomain,
+    /// A few edits, another TLD or an added word, e.g. `paypa.com`, `paypal.shop` or `paypal-login.com`.
+    Typosquat,
+}
+
+/// Suspicion that the verified origin impersonates a protected domain or a domain that is claimed in its own
+/// credentials.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[non_exhaustive]
+pub struct Impersonation {
+    /// Host of the origin in Unicode, e.g. `pаypal.com`.
+    pub host: String,
+    pub kind: ImpersonationKind,
+    /// `SuspectedImpersonation` with the details of the suspicion.
+    pub result: VerificationResult,
+    /// Domain or brand that the host resembles. Absent for `mixed-script`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+/// Configuration of the detection of lookalike domains.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ImpersonationConfig {
+    /// Domains, e.g. `paypal.com`, or brand names without TLD, e.g. `paypal`, that are protected against
+    /// impersonation. Domains that the credentials of the origin claim are protected as well.
+    pub protected_domains: Vec<String>,
+    /// Maximum number of edits between the names of a typosquat and its target.
+    pub max_distance: usize,
+    /// A suspected impersonation fails the verification, otherwise it's only reported.
+    pub override_verified: bool,
+}
+
+impl Default for ImpersonationConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            protected_domains: Vec::new(),
+            max_distance: DEFAULT_MAX_DISTANCE,
+            override_verified: true,
+        }
+    }
+}
+
+impl ImpersonationConfig {
+    /// Protects the given domains and brands.
+    #[inline]
+    #[must_use]
+    pub fn with_protected_domains(mut self, protected_domains: Vec<String>) -> Self {
+        self.protected_domains = protected_domains;
+        self
+    }
+
+    /// Compares the verified origin with the protected domains and the domains claimed in the valid credentials and
+    /// records a suspected impersonation. The verification fails if the config overrides `verified`.
+    #[inline]
+    pub fn check(&self, dto: &mut GroupedVerificationResponseDto) {
+        let Some(host) = dto
+            .origin
+            .as_deref()
+            .and_then(|origin| Url::parse(origin).ok())
+            .and_then(|origin| origin.host_str().map(str::to_owned))
+        else {
+            return;
+        };
+        let claimed: Vec<String> = dto
+            .credentials()
+            .filter(|credential| matches!(credential.result, VerificationResult::VcValid(_)))
+            .flat_map(|credential| claimed_domains(&credential.credential))
+            .collect();
+        dto.impersonation = self.detect(&host, &claimed);
+        if dto.impersonation.is_some() && self.override_verified {
+            dto.verified = false;
+        }
+    }
+
+    /// Returns the suspected impersonation of the host, given in punycode, and the domains that its credentials
+    /// claim.
+    ///
+    /// Hosts that are a protected domain or one of its subdomains are never suspicious, claimed domains that cover
+    /// the host are skipped. The registrable domain is approximated
+    /// by the last two labels, three below second-level suffixes like `co.uk`.
+    #[inline]
+    #[must_use]
+    pub fn detect(&self, host: &str, claimed: &[String]) -> Option<Impersonation> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let protected: Vec<String> = self
+            .protected_domains
+            .iter()
+            .filter_map(|target| idna::domain_to_ascii(target.trim()).ok())
+            .filter(|target| !target.is_empty())
+            .collect();
+        if protected
+            .iter()
+            .any(|target| target.contains('.') && covers(target, &host))
+        {
+            return None;
+        }
+        // claimed domains may be self-issued, thus they never exempt the host from the comparison with the
+        // protected domains
+        let targets: Vec<String> = protected
+            .into_iter()
+            .chain(
+                claimed
+                    .iter()
+                    .filter(|target| !target.is_empty() && !covers(target, &host))
+                    .cloned(),
+            )
+            .collect();
+
+        let (unicode, _) = idna::domain_to_unicode(&host);
+        let registrable = registrable_domain(&unicode);
+        let name = registrable.split('.').next().unwrap_or_default();
+        let subdomain = unicode
+            .strip_suffix(registrable)
+            .unwrap_or_default()
+            .trim_end_matches('.');
+        for target in &targets {
+            let (target, _) = idna::domain_to_unicode(target);
+            let (target_registrable, target_name) = if target.contains('.') {
+                let registrable = registrable_domain(&target);
+                (
+                    Some(registrable),
+                    registrable.split('.').next().unwrap_or_default(),
+                )
+            } else {
+                (None, target.as_str())
+            };
+            // same registrable domain, same owner
+            if target_registrable == Some(registrable)
+                || (target_registrable.is_none() && name == target_name)
+            {
+                continue;
+            }
+
+            let target_prototype = prototype(target_name);
+            let kind = if name != target_name && prototype(name) == target_prototype {
+                Some(ImpersonationKind::Homoglyph)
+            } else if subdomain
+                .split(['.', '-'])
+                .any(|token| prototype(token) == target_prototype)
+                || target_registrable
+                    .is_some_and(|target| subdomain.contains(&target.replace('.', "-")))
+            {
+                Some(ImpersonationKind::Subdomain)
+            } else if name == target_name
+                || name
+                    .split('-')
+                    .any(|token| prototype(token) == target_prototype)
+                || prototype(&name.replace('-', "")) == target_prototype
+                || (target_name.chars().count() >= MIN_TYPOSQUAT_LENGTH
+                    && distance(&prototype(name), &target_prototype) <= self.max_distance)
+            {
+                Some(ImpersonationKind::Typosquat)
+            } else {
+                None
+            };
+            if let Some(kind) = kind {
+                return Some(Impersonation {
+                    result: VerificationResult::suspected_impersonation(format!(
+                        "{unicode} resembles {target} ({})",
+                        kind_name(kind)
+                    )),
+                    host: unicode,
+                    kind,
+                    target: Some(target),
+                });
+            }
+        }
+
+        unicode
+            .split('.')
+            .any(|label| {
+                !label.is_single_script()
+                    && label.chars().any(is_potential_mixed_script_confusable_char)
+            })
+            .then(|| Impersonation {
+                result: VerificationResult::suspected_impersonation(format!(
+                    "{unicode} mixes scripts with confusable characters"
+                )),
+                host: unicode,
+                kind: ImpersonationKind::MixedScript,
+                target: None,
+            })
+    }
+}
+
+/// Returns the name of the kind as it's serialized.
+const fn kind_name(kind: ImpersonationKind) -> &'static str {
+    match kind {
+        ImpersonationKind::Homoglyph => "homoglyph",
+        ImpersonationKind::MixedScript => "mixed-script",
+        ImpersonationKind::Subdomain => "subdomain",
+        ImpersonationKind::Typosquat => "typosquat",
+    }
+}
+
+/// Returns the registrable part of the domain, e.g. `example.co.uk` for `shop.example.co.uk`.
+fn registrable_domain(domain: &str) -> &str {
+    let labels: Vec<&str> = domain.split('.').collect();
+    let count = match labels.as_slice() {
+        [.., _, second, tld] if tld.len() == 2 && SECOND_LEVEL_SUFFIXES.contains(second) => 3,
+        _ => 2,
+    };
+    let skip: usize = labels
+        .iter()
+        .take(labels.len().saturating_sub(count))
+        .map(|label| label.len() + 1)
+        .sum();
+    &domain[skip..]
+}
+
+/// Maps confusable characters to their prototype as defined by Unicode TS #39, e.g. `pаypal` (Cyrillic `а`) and
+/// `paypa1` both become `paypal`.
+fn prototype(text: &str) -> String {
+    skeleton(&text.to_lowercase())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Returns the number of insertions, deletions, substitutions and transpositions of adjacent characters that turn
+/// one text into the other (optimal string alignment distance).
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut value = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                value = value.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = value;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(config: &ImpersonationConfig, host: &str) -> Option<ImpersonationKind> {
+        let host = idna::domain_to_ascii(host).unwrap();
+        config
+            .detect(&host, &[])
+            .map(|impersonation| impersonation.kind)
+    }
+
+    #[test]
+    fn test_detect() {
+        let config = ImpersonationConfig::default()
+            .with_protected_domains(vec!["paypal.com".to_owned(), "amazon".to_owned()]);
+
+        // the targets themselves and unrelated domains
+        assert_eq!(kind(&config, "paypal.com"), None);
+        assert_eq!(kind(&config, "www.paypal.com"), None);
+        assert_eq!(kind(&config, "amazon.de"), None);
+        assert_eq!(kind(&config, "shop.example"), None);
+
+        assert_eq!(
+            kind(&config, "pаypal.com"),
+            Some(ImpersonationKind::Homoglyph)
+        );
+        assert_eq!(
+            kind(&config, "paypa1.com"),
+            Some(ImpersonationKind::Homoglyph)
+        );
+        assert_eq!(
+            kind(&config, "arnazon.de"),
+            Some(ImpersonationKind::Homoglyph)
+        );
+        assert_eq!(
+            kind(&config, "paypal.com.login.example"),
+            Some(ImpersonationKind::Subdomain)
+        );
+        assert_eq!(
+            kind(&config, "secure-amazon.shop.example"),
+            Some(ImpersonationKind::Subdomain)
+        );
+        assert_eq!(
+            kind(&config, "paypl.com"),
+            Some(ImpersonationKind::Typosquat)
+        );
+        assert_eq!(
+            kind(&config, "pyapal.com"),
+            Some(ImpersonationKind::Typosquat)
+        );
+        assert_eq!(
+            kind(&config, "paypal.shop"),
+            Some(ImpersonationKind::Typosquat)
+        );
+        assert_eq!(
+            kind(&config, "paypal-login.com"),
+            Some(ImpersonationKind::Typosquat)
+        );
+        assert_eq!(
+            kind(&config, "amaz0n-deals.co.uk"),
+            Some(ImpersonationKind::Typosquat)
+        );
+        assert_eq!(
+            kind(&config, "рaypаl.example"),
+            Some(ImpersonationKind::Homoglyph)
+        );
+        assert_eq!(
+            kind(&config, "gооgle.example"),
+            Some(ImpersonationKind::MixedScript)
+        );
+
+        let impersonation = config.detect("xn--pypal-4ve.com", &[]).unwrap();
+        assert_eq!(impersonation.host, "pаypal.com");
+        assert_eq!(impersonation.target.as_deref(), Some("paypal.com"));
+        assert_eq!(impersonation.result.payload().code, 1 << 19);
+    }
+
+    #[test]
+    fn test_check() {
+        let verification = |origin| crate::dto::test_util::grouped_dto(origin, Vec::new());
+        let mut config =
+            ImpersonationConfig::default().with_protected_domains(vec!["example.org".to_owned()]);
+
+        let mut dto = verification(Some("https://example.com"));
+        config.check(&mut dto);
+        let impersonation = dto.impersonation.as_ref().unwrap();
+        assert_eq!(impersonation.kind, ImpersonationKind::Typosquat);
+        assert_eq!(impersonation.host, "example.com");
+        assert_eq!(impersonation.target.as_deref(), Some("example.org"));
+        assert!(!dto.verified);
+
+        // reported only
+        config.override_verified = false;
+        let mut dto = verification(Some("https://example.com"));
+        config.check(&mut dto);
+        assert!(dto.impersonation.is_some());
+        assert!(dto.verified);
+
+        // DIDs that have been verified directly have no origin to compare
+        let mut dto = verification(None);
+        config.check(&mut dto);
+        assert!(dto.impersonation.is_none());
+    }
+
+    #[test]
+    fn test_detect_claimed_domains() {
+        let config = ImpersonationConfig::default();
+        let claimed = vec!["shop.example".to_owned()];
+        assert!(config.detect("shop.example", &claimed).is_none());
+        assert!(config.detect("checkout.shop.example", &claimed).is_none());
+        let impersonation = config.detect("sh0p.example", &claimed).unwrap();
+        assert_eq!(impersonation.kind, ImpersonationKind::Homoglyph);
+    }
+
+    #[test]
+    fn test_detect_self_claimed_lookalike() {
+        // a lookalike can't exempt itself by claiming its own domain in a self-issued credential
+        let config =
+            ImpersonationConfig::default().with_protected_domains(vec!["paypal.com".to_owned()]);
+        let impersonation = config
+            .detect("paypa1.com", &["paypa1.com".to_owned()])
+            .unwrap();
+        assert_eq!(impersonation.kind, ImpersonationKind::Homoglyph);
+        assert_eq!(impersonation.target.as_deref(), Some("paypal.com"));
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        assert_eq!(registrable_domain("shop.example.com"), "example.com");
+        assert_eq!(registrable_domain("shop.example.co.uk"), "example.co.uk");
+        assert_eq!(registrable_domain("example"), "example");
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("paypal", "paypal"), 0);
+        assert_eq!(distance("paypal", "paypl"), 1);
+        assert_eq!(distance("paypal", "pyapal"), 1);
+        assert_eq!(distance("paypal", "amazon"), 6);
+    }
+}
@@ -7,22 +7,38 @@
     reason = "Simplification of code"
 )]
 
+pub mod cache;
+pub mod concurrency;
+pub mod consistency;
+pub mod dns;
 pub mod dto;
+pub mod fetch;
+pub mod http_cache;
+pub mod identity;
+pub mod impersonation;
+pub mod lists;
+pub mod policy;
+pub mod presentation;
+pub mod resolver;
+pub mod trust;
+pub mod warnings;
 use dto::VerificationResult;
+use presentation::EncodedPresentation;
+use resolver::DidResolver;
 use serde::Deserialize;
 use ssi::claims::vc::v1::JsonCredential;
-#[cfg(not(test))]
-use ssi::dids::AnyDidMethod;
 #[cfg(test)]
-use ssi::dids::{self, StaticDIDResolver};
-#[cfg(test)]
-use ssi::prelude::DIDResolver as _;
+use ssi::dids;
 use ssi::{
     claims::{
         chrono::Utc,
         data_integrity::AnyDataIntegrity,
-        vc::v1::{data_integrity::any_credential_from_json_str, JsonPresentation},
-        VerificationParameters,
+        jwt::ToDecodedJwt as _,
+        vc::v1::{
+            data_integrity::any_credential_from_json_str, decode_jwt_vc_claims,
+            decode_jwt_vp_claims, JsonPresentation,
+        },
+        JwsStr, VerificationParameters,
     },
     dids::{DIDBuf, VerificationMethodDIDResolver},
     json_ld::syntax::Value,
@@ -39,10 +55,9 @@ pub struct WellKnownDidConfig {
     pub linked_dids: Vec<JsonCredential>,
 }
 
-/// Creates a verifier for VCs and VPs that uses a static DID resolver which knows about the
-/// DIDs used in the credentials available in the tests/ directory.
+/// Creates a DID resolver which knows about the DIDs used in the credentials available in the tests/ directory.
 #[cfg(test)]
-fn static_test_resovler() -> VerificationMethodDIDResolver<StaticDIDResolver, AnyMethod> {
+fn static_test_resovler() -> DidResolver {
     let did_holder = include_str!("../tests/dids/did-holder")
         .trim()
         .parse()
@@ -59,14 +74,10 @@ fn static_test_resovler() -> VerificationMethodDIDResolver<StaticDIDResolver, An
         include_bytes!("../tests/dids/did-doc-trust-party.json").to_vec(),
         Some("application/did+json".to_owned()),
     );
-    // Create a static DID resolver that resolves our test DID into a
-    // static DID document
-    let mut did_resolver = StaticDIDResolver::new();
-    did_resolver.insert(did_holder, did_doc_holder);
-    did_resolver.insert(did_tp, did_doc_tp);
-    // Turn the DID resolver into a verification method resolver by setting
-    // resolution options
-    did_resolver.into_vm_resolver()
+    // Create a DID resolver that resolves our test DIDs into static DID documents
+    DidResolver::default()
+        .with_document(did_holder, did_doc_holder)
+        .with_document(did_tp, did_doc_tp)
 }
 
 /// Verifies the given DID configuration.
@@ -82,6 +93,17 @@ fn static_test_resovler() -> VerificationMethodDIDResolver<StaticDIDResolver, An
 pub async fn verify_did_configuration_vc(
     did_configuration_json: &String,
     url: &Url,
+) -> Result<VerificationResult, VerificationResult> {
+    verify_did_configuration_vc_with(did_configuration_json, url, &default_resolver()).await
+}
+
+/// Verifies the given DID configuration like [`verify_did_configuration_vc`] but resolves DIDs with the given
+/// resolver.
+#[inline]
+pub async fn verify_did_configuration_vc_with(
+    did_configuration_json: &String,
+    url: &Url,
+    resolver: &DidResolver,
 ) -> Result<VerificationResult, VerificationResult> {
     // TODO: add support for JWT credentials
     // TODO: what if multiple credentials are available, is this handled properly?
@@ -92,7 +114,7 @@ pub async fn verify_did_configuration_vc(
             let issuer = config.linked_dids[0].issuer.id().as_bytes().to_vec();
             let issuer_did = DIDBuf::new(issuer)
                 .map_err(|_e| VerificationResult::did_config_error("issuer is not a DID".into()))?;
-            match verify_vc(&domain_linkage_vc_json, &issuer_did, false).await {
+            match verify_vc_with(&domain_linkage_vc_json, &issuer_did, false, resolver).await {
                 Ok(_) => {
                     // The credentialSubject.origin property MUST be present,
                     // and its value MUST match the origin the resource was requested from.
@@ -100,9 +122,7 @@ pub async fn verify_did_configuration_vc(
                         .get("origin")
                         .next()
                         .and_then(|value| match value {
-                            Value::String(origin) => {
-                                (*origin == url.origin().ascii_serialization()).then_some(origin)
-                            }
+                            Value::String(origin) => origin_matches(origin, url).then_some(origin),
                             Value::Null | Value::Boolean(_) | Value::Number(_) | Value::Array(_) | Value::Object(_) => None,
                         })
                         .ok_or_else(|| VerificationResult::did_config_error(
@@ -135,7 +155,11 @@ pub async fn verify_did_configuration_vc(
                     | VerificationResult::VcValidationErrorPremature(_)
                     | VerificationResult::VcValidationErrorExpired(_)
                     | VerificationResult::VcValidationErrorMissingIssuance(_)
-                    | VerificationResult::DidConfigError(_) => error.into_result(),
+                    | VerificationResult::DidConfigError(_)
+                    | VerificationResult::DidDeactivated(_)
+                    | VerificationResult::VcConsistencyError(_)
+                    | VerificationResult::SuspectedImpersonation(_)
+                    | VerificationResult::Blocked(_) => error.into_result(),
                 },
             }
         }
@@ -143,6 +167,12 @@ pub async fn verify_did_configuration_vc(
     }
 }
 
+/// Returns `true` if the origin of a Domain Linkage Credential is the origin of the URL. Internationalized domain names
+/// are compared in their punycode representation, so `https://bücher.example` matches `https://xn--bcher-kva.example`.
+fn origin_matches(origin: &str, url: &Url) -> bool {
+    Url::parse(origin).is_ok_and(|origin| origin.origin() == url.origin())
+}
+
 /// Verifies the given VC and validates the contained claims.
 ///
 /// I.e. checks the cryptographic proof and verifies that the claims themselves
@@ -153,6 +183,23 @@ pub async fn verify_vc(
     vc_json: &str,
     expected_subject: &DIDBuf,
     allow_missing_subjectid: bool,
+) -> Result<VerificationResult, VerificationResult> {
+    verify_vc_with(
+        vc_json,
+        expected_subject,
+        allow_missing_subjectid,
+        &default_resolver(),
+    )
+    .await
+}
+
+/// Verifies the given VC like [`verify_vc`] but resolves DIDs with the given resolver.
+#[inline]
+pub async fn verify_vc_with(
+    vc_json: &str,
+    expected_subject: &DIDBuf,
+    allow_missing_subjectid: bool,
+    resolver: &DidResolver,
 ) -> Result<VerificationResult, VerificationResult> {
     let vc = match any_credential_from_json_str(vc_json) {
         Ok(c) => c,
@@ -161,8 +208,12 @@ pub async fn verify_vc(
 
     // Prepare our verifier
     // TODO can we avoid doing this with every verify_vc invocation?
-    let verifier = create_verifier();
-    match vc.verify(&verifier).await {
+    let verifier = create_verifier(resolver);
+    let verification = {
+        let _permit = concurrency::acquire_verification().await;
+        vc.verify(&verifier).await
+    };
+    match verification {
         Ok(Ok(())) => {
             // The credentialSubject.id MUST be a DID,
             let id = &vc.credential_subjects[0]
@@ -176,31 +227,94 @@ pub async fn verify_vc(
                     | Value::Array(_)
                     | Value::Object(_) => None,
                 });
-            if allow_missing_subjectid && id.is_none() {
-                VerificationResult::vc_valid().into_result()
-            } else {
-                let did = id.clone().ok_or_else(|| {
-                    VerificationResult::vc_validation_error_other("Subject must be a DID".into())
-                })?;
-
-                // and the value MUST be equal to the Issuer of the Domain Linkage Credential.
-                if did == *expected_subject {
-                    VerificationResult::vc_valid().into_result()
-                } else {
-                    VerificationResult::vc_validation_error_subject_mismatch(format!(
-                        "Expected '{}' but found '{}'",
-                        expected_subject.as_uri(),
-                        did.as_uri()
-                    ))
-                    .into_result()
-                }
-            }
+            check_subject(id.as_ref(), expected_subject, allow_missing_subjectid)
         }
         Ok(Err(error)) => VerificationResult::from(error).into_result(),
         Err(error) => VerificationResult::vc_proof_error(error.to_string()).into_result(),
     }
 }
 
+/// Verifies the given VC in JWT form like [`verify_vc_with`]. The credential must be signed with a key of its issuer.
+#[inline]
+pub async fn verify_jwt_vc_with(
+    vc_jwt: &str,
+    expected_subject: &DIDBuf,
+    allow_missing_subjectid: bool,
+    resolver: &DidResolver,
+) -> Result<VerificationResult, VerificationResult> {
+    let jwt = match JwsStr::new(vc_jwt) {
+        Ok(jws) => jws
+            .to_decoded_jwt()
+            .map_err(|e| VerificationResult::vc_parse_error(e.to_string()))?,
+        Err(e) => return VerificationResult::vc_parse_error(e.to_string()).into_result(),
+    };
+    let verifier = create_verifier(resolver);
+    let verification = {
+        let _permit = concurrency::acquire_verification().await;
+        jwt.verify(&verifier).await
+    };
+    match verification {
+        Ok(Ok(())) => {}
+        Ok(Err(error)) => return VerificationResult::from(error).into_result(),
+        Err(error) => return VerificationResult::vc_proof_error(error.to_string()).into_result(),
+    }
+    let key_controller = key_controller(jwt.signing_bytes.header.key_id.as_deref());
+    let vc: serde_json::Value = decode_jwt_vc_claims(jwt.signing_bytes.payload)
+        .map_err(|e| VerificationResult::vc_parse_error(e.to_string()))?;
+    let issuer = match vc.get("issuer") {
+        Some(serde_json::Value::Object(issuer)) => issuer.get("id"),
+        issuer => issuer,
+    }
+    .and_then(serde_json::Value::as_str);
+    // Otherwise, anyone could issue a credential in the name of the issuer
+    if key_controller.is_none() || key_controller != issuer {
+        return VerificationResult::vc_proof_error_key_mismatch(
+            "Key of credential must belong to the issuer".to_owned(),
+        )
+        .into_result();
+    }
+    let subject = match vc.get("credentialSubject") {
+        Some(serde_json::Value::Array(subjects)) => subjects.first(),
+        subject => subject,
+    };
+    let id = subject
+        .and_then(|subject| subject.get("id"))
+        .and_then(serde_json::Value::as_str)
+        .and_then(|id| DIDBuf::new(id.as_bytes().to_vec()).ok());
+    check_subject(id.as_ref(), expected_subject, allow_missing_subjectid)
+}
+
+/// Checks that the id of the credential subject is the expected DID.
+fn check_subject(
+    id: Option<&DIDBuf>,
+    expected_subject: &DIDBuf,
+    allow_missing_subjectid: bool,
+) -> Result<VerificationResult, VerificationResult> {
+    if allow_missing_subjectid && id.is_none() {
+        return VerificationResult::vc_valid().into_result();
+    }
+    let did = id.ok_or_else(|| {
+        VerificationResult::vc_validation_error_other("Subject must be a DID".into())
+    })?;
+
+    // and the value MUST be equal to the Issuer of the Domain Linkage Credential.
+    if did == expected_subject {
+        VerificationResult::vc_valid().into_result()
+    } else {
+        VerificationResult::vc_validation_error_subject_mismatch(format!(
+            "Expected '{}' but found '{}'",
+            expected_subject.as_uri(),
+            did.as_uri()
+        ))
+        .into_result()
+    }
+}
+
+/// Returns the DID that controls the key with the given id, i.e. the id without its fragment.
+fn key_controller(key_id: Option<&str>) -> Option<&str> {
+    key_id.map(|key_id| key_id.split_once('#').map_or(key_id, |(did, _)| did))
+}
+
 /// Verifies the given Verifiable Presentations.
 ///
 /// After verifying the proof of each presentation the nested Verifiable
@@ -214,12 +328,24 @@ pub async fn verify_vc(
 pub async fn verify_presentations(
     presentations: Vec<JsonPresentation>,
     did: &DIDBuf,
+) -> Vec<VerificationResult> {
+    verify_presentations_with(presentations, did, &default_resolver()).await
+}
+
+/// Verifies the given Verifiable Presentations like [`verify_presentations`] but resolves DIDs with the given
+/// resolver.
+#[inline]
+pub async fn verify_presentations_with(
+    presentations: Vec<JsonPresentation>,
+    did: &DIDBuf,
+    resolver: &DidResolver,
 ) -> Vec<VerificationResult> {
     // Prepare verification tasks for each presentation
     let tasks: JoinSet<_> = presentations
         .into_iter()
         .map(|vp| {
             let did_clone = did.clone();
+            let resolver_clone = resolver.clone();
             async move {
                 // TODO find more performant way to transfrom JsonPresentation to AnyDataIntegrity
                 // without serialization roundtrips
@@ -227,15 +353,17 @@ pub async fn verify_presentations(
                 // it should be safe to unwrap the result as we just deserialized the whole VP
                 // => serializing the VP should work without errors
                 match serde_json::to_string(&vp) {
-                    Ok(vp_json) => match verify_vp(&vp_json, &did_clone, true).await {
-                        Ok(results) => results,
-                        // On error, something was wrong with the VP. We expand that error for each VC.
-                        Err(vp_error) => vp
-                            .verifiable_credentials
-                            .iter()
-                            .map(|_| vp_error.clone())
-                            .collect(),
-                    },
+                    Ok(vp_json) => {
+                        match verify_vp_with(&vp_json, &did_clone, true, &resolver_clone).await {
+                            Ok(results) => results,
+                            // On error, something was wrong with the VP. We expand that error for each VC.
+                            Err(vp_error) => vp
+                                .verifiable_credentials
+                                .iter()
+                                .map(|_| vp_error.clone())
+                                .collect(),
+                        }
+                    }
                     Err(e) => vec![VerificationResult::vp_parse_error(e.to_string())],
                 }
             }
@@ -252,6 +380,17 @@ pub async fn verify_vp(
     vp_json: &str,
     expected_holder: &DIDBuf,
     verify_vcs: bool,
+) -> Result<Vec<VerificationResult>, VerificationResult> {
+    verify_vp_with(vp_json, expected_holder, verify_vcs, &default_resolver()).await
+}
+
+/// Verifies the given Verifiable Presentation like [`verify_vp`] but resolves DIDs with the given resolver.
+#[inline]
+pub async fn verify_vp_with(
+    vp_json: &str,
+    expected_holder: &DIDBuf,
+    verify_vcs: bool,
+    resolver: &DidResolver,
 ) -> Result<Vec<VerificationResult>, VerificationResult> {
     // Create DataIntegrity from JSON string
     let vp: AnyDataIntegrity<JsonPresentation> = match serde_json::from_str(vp_json) {
@@ -259,8 +398,13 @@ pub async fn verify_vp(
         Err(e) => return VerificationResult::vp_parse_error(e.to_string()).into_vec_result(),
     };
     // Verify the presentation's proof
-    let verifier = create_verifier();
-    match vp.verify(&verifier).await {
+    let verifier = create_verifier(resolver);
+    // The permit is released before the nested credentials are verified
+    let verification = {
+        let _permit = concurrency::acquire_verification().await;
+        vp.verify(&verifier).await
+    };
+    match verification {
         Err(proof_err) => {
             return VerificationResult::vp_proof_error(proof_err.to_string()).into_vec_result()
         }
@@ -288,6 +432,7 @@ pub async fn verify_vp(
             .enumerate()
             .map(|(i, vc)| {
                 let holder_clone = expected_holder.clone();
+                let resolver_clone = resolver.clone();
                 async move {
                     // TODO find more performant way to transfrom SpecializedJsonCredential to AnyDataIntegrity
                     // without serialization roundtrips
@@ -296,7 +441,14 @@ pub async fn verify_vp(
                     // => serializing the VC should work without errors
                     match serde_json::to_string(&vc) {
                         Ok(vc_json_data) => {
-                            match verify_vc(&vc_json_data, &holder_clone, true).await {
+                            match verify_vc_with(
+                                &vc_json_data,
+                                &holder_clone,
+                                true,
+                                &resolver_clone,
+                            )
+                            .await
+                            {
                                 Err(r) | Ok(r) => (i, r),
                             }
                         }
@@ -314,27 +466,131 @@ pub async fn verify_vp(
     VerificationResult::vp_valid().into_vec_result()
 }
 
+/// Verifies the given Verifiable Presentation in JWT form like [`verify_vp_with`]. The presentation must be signed
+/// with a key of the holder. The nested credentials may be in JWT form, too.
+#[inline]
+pub async fn verify_jwt_vp_with(
+    vp_jwt: &str,
+    expected_holder: &DIDBuf,
+    verify_vcs: bool,
+    resolver: &DidResolver,
+) -> Result<Vec<VerificationResult>, VerificationResult> {
+    let jwt = match JwsStr::new(vp_jwt) {
+        Ok(jws) => jws
+            .to_decoded_jwt()
+            .map_err(|e| VerificationResult::vp_parse_error(e.to_string()))?,
+        Err(e) => return VerificationResult::vp_parse_error(e.to_string()).into_vec_result(),
+    };
+    let verifier = create_verifier(resolver);
+    let verification = {
+        let _permit = concurrency::acquire_verification().await;
+        jwt.verify(&verifier).await
+    };
+    match verification {
+        Err(proof_err) => {
+            return VerificationResult::vp_proof_error(proof_err.to_string()).into_vec_result()
+        }
+        Ok(Err(verification_err)) => {
+            return VerificationResult::vp_verification_error(verification_err.to_string())
+                .into_vec_result()
+        }
+        Ok(Ok(())) => {
+            // go on
+        }
+    }
+    // Otherwise, anyone could sign a presentation in the name of the holder
+    if key_controller(jwt.signing_bytes.header.key_id.as_deref()) != Some(expected_holder.as_str())
+    {
+        return VerificationResult::vp_verification_error(
+            "Key of presentation must belong to DID".to_owned(),
+        )
+        .into_vec_result();
+    }
+    let vp: serde_json::Value = decode_jwt_vp_claims(jwt.signing_bytes.payload)
+        .map_err(|e| VerificationResult::vp_parse_error(e.to_string()))?;
+    if vp.get("holder").and_then(serde_json::Value::as_str) != Some(expected_holder.as_str()) {
+        return VerificationResult::vp_verification_error(
+            "Holder of presentation must match DID".to_owned(),
+        )
+        .into_vec_result();
+    }
+    if verify_vcs {
+        let credentials = match vp.get("verifiableCredential") {
+            Some(serde_json::Value::Array(credentials)) => credentials.clone(),
+            Some(credential) => vec![credential.clone()],
+            None => Vec::new(),
+        };
+        let tasks: JoinSet<_> = credentials
+            .into_iter()
+            .enumerate()
+            .map(|(i, vc)| {
+                let holder_clone = expected_holder.clone();
+                let resolver_clone = resolver.clone();
+                async move {
+                    let result = match vc {
+                        serde_json::Value::String(vc_jwt) => {
+                            verify_jwt_vc_with(&vc_jwt, &holder_clone, true, &resolver_clone).await
+                        }
+                        vc => {
+                            verify_vc_with(&vc.to_string(), &holder_clone, true, &resolver_clone)
+                                .await
+                        }
+                    };
+                    match result {
+                        Err(r) | Ok(r) => (i, r),
+                    }
+                }
+            })
+            .collect();
+        let mut task_results = tasks.join_all().await;
+        task_results.sort_by_key(|item| item.0);
+        let sorted_results: Vec<VerificationResult> =
+            task_results.into_iter().map(|(_, vc)| vc).collect();
+        return Ok(sorted_results);
+    }
+    VerificationResult::vp_valid().into_vec_result()
+}
+
+/// Verifies the given presentation with [`verify_vp_with`] or, if it's in JWT form, with [`verify_jwt_vp_with`].
+#[inline]
+pub async fn verify_encoded_vp_with(
+    presentation: &EncodedPresentation,
+    expected_holder: &DIDBuf,
+    verify_vcs: bool,
+    resolver: &DidResolver,
+) -> Result<Vec<VerificationResult>, VerificationResult> {
+    match presentation {
+        EncodedPresentation::Ldp(vp) => match serde_json::to_string(vp) {
+            Ok(vp_json) => verify_vp_with(&vp_json, expected_holder, verify_vcs, resolver).await,
+            Err(e) => VerificationResult::vp_parse_error(e.to_string()).into_vec_result(),
+        },
+        EncodedPresentation::Jwt(vp_jwt, _) => {
+            verify_jwt_vp_with(vp_jwt, expected_holder, verify_vcs, resolver).await
+        }
+    }
+}
+
+/// Creates a verifier for VCs and VPs that uses the given resolver to resolve DIDs.
+/// The verifier will use the current date/time when validating dates.
+fn create_verifier(
+    resolver: &DidResolver,
+) -> VerificationParameters<VerificationMethodDIDResolver<DidResolver, AnyMethod>> {
+    let resolver = VerificationMethodDIDResolver::<_, AnyMethod>::new(resolver.clone());
+    // Create a verifier using the verification method resolver
+    let verifier = VerificationParameters::from_resolver(resolver);
+    verifier.with_date_time(Utc::now())
+}
+
 cfg_if::cfg_if! {
      if #[cfg(test)] {
-        /// Creates a verifier for VCs and VPs that uses AnyDidMethod to resolve DIDs.
-        /// The verifier will use the current date/time when validating dates.
-        fn create_verifier(
-        ) -> VerificationParameters<VerificationMethodDIDResolver<StaticDIDResolver, AnyMethod>> {
-            let resolver = static_test_resovler();
-            // Create a verifier using the verification method resolver
-            let verifier = VerificationParameters::from_resolver(resolver);
-            verifier.with_date_time(Utc::now())
+        /// Returns the resolver that knows about the DIDs in the tests/ directory.
+        fn default_resolver() -> DidResolver {
+            static_test_resovler()
         }
     } else {
-        /// Creates a verifier for VCs and VPs that uses AnyDidMethod to resolve DIDs.
-        /// The verifier will use the current date/time when validating dates.
-        fn create_verifier(
-        ) -> VerificationParameters<VerificationMethodDIDResolver<AnyDidMethod, AnyMethod>> {
-            let resolver =
-                VerificationMethodDIDResolver::<_, AnyMethod>::new(AnyDidMethod::default());
-            // Create a verifier using the verification method resolver
-            let verifier = VerificationParameters::from_resolver(resolver);
-            verifier.with_date_time(Utc::now())
+        /// Returns the resolver that resolves all DIDs via AnyDidMethod.
+        fn default_resolver() -> DidResolver {
+            DidResolver::default()
         }
     }
 }
@@ -485,6 +741,69 @@ mod tests {
         assert!(matches!(x, VerificationResult::VpVerificationError(_)));
     }
 
+    fn read_jwt(path: &str) -> String {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn verify_jwt_vc() {
+        let vc_jwt = read_jwt("tests/credentials/credential-trust-party-issued-jwt.json");
+        let resolver = default_resolver();
+        assert!(matches!(
+            verify_jwt_vc_with(&vc_jwt, &holder_did(), false, &resolver)
+                .await
+                .unwrap(),
+            VerificationResult::VcValid(_)
+        ));
+        let did = DIDBuf::new(b"did:example:foo".to_vec()).unwrap();
+        assert!(matches!(
+            verify_jwt_vc_with(&vc_jwt, &did, false, &resolver)
+                .await
+                .unwrap_err(),
+            VerificationResult::VcValidationErrorSubjectMismatch(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn verify_jwt_vp() {
+        let vp_jwt = read_jwt("tests/presentations/presentation-jwt.json");
+        let x = verify_jwt_vp_with(&vp_jwt, &holder_did(), true, &default_resolver())
+            .await
+            .unwrap();
+        assert_eq!(x.len(), 2);
+        assert!(matches!(x[0], VerificationResult::VcValid(_)));
+        assert!(matches!(x[1], VerificationResult::VcValid(_)));
+    }
+
+    #[tokio::test]
+    async fn verify_jwt_vp_tampered() {
+        let vp_jwt = read_jwt("tests/presentations/presentation-jwt.json");
+        let (signing_bytes, signature) = vp_jwt.rsplit_once('.').unwrap();
+        let tampered = format!(
+            "{signing_bytes}.{}{}",
+            if signature.starts_with('A') { 'B' } else { 'A' },
+            &signature[1..]
+        );
+        let x = verify_jwt_vp_with(&tampered, &holder_did(), true, &default_resolver())
+            .await
+            .unwrap_err();
+        assert!(matches!(x, VerificationResult::VpProofError(_)));
+    }
+
+    #[tokio::test]
+    async fn verify_jwt_vp_bad_holder() {
+        let vp_jwt = read_jwt("tests/presentations/presentation-jwt.json");
+        let did = DIDBuf::new(b"did:example:foo".to_vec()).unwrap();
+        let x = verify_jwt_vp_with(&vp_jwt, &did, true, &default_resolver())
+            .await
+            .unwrap_err();
+        assert!(matches!(x, VerificationResult::VpVerificationError(_)));
+        let x = verify_jwt_vp_with("not a JWT", &holder_did(), true, &default_resolver())
+            .await
+            .unwrap_err();
+        assert!(matches!(x, VerificationResult::VpParseError(_)));
+    }
+
     #[tokio::test]
     async fn verify_did_config() {
         let did_config_json =
@@ -538,6 +857,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_origin_matches() {
+        let url = Url::parse("https://xn--bcher-kva.example/shop").unwrap();
+        assert!(origin_matches("https://xn--bcher-kva.example", &url));
+        assert!(origin_matches("https://bücher.example", &url));
+        assert!(origin_matches("https://BÜCHER.example:443", &url));
+        assert!(!origin_matches("https://www.bücher.example", &url));
+        assert!(!origin_matches("http://bücher.example", &url));
+        assert!(!origin_matches("bücher.example", &url));
+
+        let url = Url::parse("https://example.com:8443/shop").unwrap();
+        assert!(origin_matches("https://example.com:8443", &url));
+        assert!(!origin_matches("https://example.com", &url));
+    }
+
     #[tokio::test]
     async fn verify_did_config_origin_not_url() {
         let did_config_json =
@@ -0,0 +1,356 @@
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::dto::VerificationResult;
+
+/// Lists could not be loaded or watched.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ListError {
+    #[error("lists could not be read: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("lists are invalid: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("lists could not be watched: {0}")]
+    Watch(#[from] notify::Error),
+}
+
+/// Entries of an allow or block list. Entries may contain `*` wildcards, e.g. `*.example.com` matches all subdomains
+/// of `example.com` but not the domain itself.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
+pub struct ListEntries {
+    /// Domains of the verified origin, in Unicode or punycode.
+    #[serde(default)]
+    pub domains: Vec<String>,
+    /// DIDs that are verified directly or have been discovered for the origin.
+    #[serde(default)]
+    pub dids: Vec<String>,
+    /// DIDs of the issuers of credentials.
+    #[serde(default)]
+    pub issuers: Vec<String>,
+}
+
+/// Allow and block lists, e.g. `{"block": {"domains": ["*.fraud.example"]}, "allow": {"dids": ["did:web:shop.example"]}}`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Lists {
+    /// Pre-approved entries. Allowed origins and DIDs skip the lookalike detection, their credentials are still
+    /// verified.
+    #[serde(default)]
+    pub allow: ListEntries,
+    /// Entries that fail the verification with `Blocked`. Block entries take precedence over allow entries.
+    #[serde(default)]
+    pub block: ListEntries,
+}
+
+/// Whether a list entry approves or blocks.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum ListAction {
+    Allow,
+    Block,
+}
+
+/// List that contains the matched entry.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum ListKind {
+    Domain,
+    Did,
+    Issuer,
+}
+
+/// Entry of an allow or block list that matched the verification.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[non_exhaustive]
+pub struct ListMatch {
+    pub action: ListAction,
+    pub list: ListKind,
+    /// `Blocked` with the matched rule. Absent for allow entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<VerificationResult>,
+    /// Entry of the list, e.g. `*.example.com`.
+    pub rule: String,
+    /// Domain or DID that matched the rule.
+    pub value: String,
+}
+
+impl Lists {
+    /// Parses lists in JSON.
+    #[inline]
+    pub fn from_json(json: &str) -> Result<Self, ListError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Returns the entry that matches the host of the origin, given in punycode.
+    #[inline]
+    #[must_use]
+    pub fn match_domain(&self, host: &str) -> Option<ListMatch> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        find(
+            ListKind::Domain,
+            &self.block.domains,
+            &self.allow.domains,
+            &host,
+            |rule| glob(&normalize_domain(rule), &host),
+        )
+    }
+
+    /// Returns the entry that matches the DID.
+    #[inline]
+    #[must_use]
+    pub fn match_did(&self, did: &str) -> Option<ListMatch> {
+        find(
+            ListKind::Did,
+            &self.block.dids,
+            &self.allow.dids,
+            did,
+            |rule| glob(rule, did),
+        )
+    }
+
+    /// Returns the entry that matches the DID of an issuer.
+    #[inline]
+    #[must_use]
+    pub fn match_issuer(&self, issuer: &str) -> Option<ListMatch> {
+        find(
+            ListKind::Issuer,
+            &self.block.issuers,
+            &self.allow.issuers,
+            issuer,
+            |rule| glob(rule, issuer),
+        )
+    }
+
+    /// Returns a hash of the entries. Verification results are only reused for the same lists.
+    #[inline]
+    #[must_use]
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_string(self)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Allow and block lists that are shared by all requests. The lists are replaced atomically when they are reloaded,
+/// requests keep the lists they started with.
+#[derive(Clone, Debug, Default)]
+pub struct AccessLists {
+    lists: Arc<RwLock<Arc<Lists>>>,
+}
+
+impl AccessLists {
+    #[inline]
+    #[must_use]
+    pub fn new(lists: Lists) -> Self {
+        Self {
+            lists: Arc::new(RwLock::new(Arc::new(lists))),
+        }
+    }
+
+    /// Loads the lists from a JSON file.
+    #[inline]
+    pub fn load(path: &Path) -> Result<Self, ListError> {
+        Ok(Self::new(Lists::from_json(&fs::read_to_string(path)?)?))
+    }
+
+    /// Returns the current lists.
+    #[inline]
+    #[must_use]
+    pub fn snapshot(&self) -> Arc<Lists> {
+        Arc::clone(&self.lists.read().expect("lists are never poisoned"))
+    }
+
+    /// Replaces the lists with the content of the file. The current lists are kept if the file is invalid.
+    #[inline]
+    pub fn reload(&self, path: &Path) -> Result<(), ListError> {
+        let lists = Lists::from_json(&fs::read_to_string(path)?)?;
+        *self.lists.write().expect("lists are never poisoned") = Arc::new(lists);
+        Ok(())
+    }
+
+    /// Reloads the lists whenever the file changes. The directory of the file is watched, so files that are replaced
+    /// by editors or Kubernetes config maps are picked up as well. The lists are watched until the watcher is dropped.
+    #[inline]
+    pub fn watch(&self, path: &Path) -> Result<RecommendedWatcher, ListError> {
+        let path = fs::canonicalize(path)?;
+        let directory = path
+            .parent()
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        let lists = self.clone();
+        let file = path.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                if matches!(event.kind, EventKind::Access(_))
+                    || !event.paths.iter().any(|changed| changed == &file)
+                {
+                    return;
+                }
+                match lists.reload(&file) {
+                    Ok(()) => println!("lists reloaded from {}", file.display()),
+                    Err(e) => {
+                        eprintln!("lists could not be reloaded, keeping the previous lists: {e}")
+                    }
+                }
+            })?;
+        watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    }
+}
+
+/// Returns the first block entry that matches the value, or else the first allow entry.
+fn find(
+    list: ListKind,
+    block: &[String],
+    allow: &[String],
+    value: &str,
+    matches: impl Fn(&str) -> bool,
+) -> Option<ListMatch> {
+    let to_match = |action, rule: &String| ListMatch {
+        action,
+        list,
+        result: (action == ListAction::Block).then(|| VerificationResult::blocked(rule.clone())),
+        rule: rule.clone(),
+        value: value.to_owned(),
+    };
+    block
+        .iter()
+        .find(|rule| matches(rule))
+        .map(|rule| to_match(ListAction::Block, rule))
+        .or_else(|| {
+            allow
+                .iter()
+                .find(|rule| matches(rule))
+                .map(|rule| to_match(ListAction::Allow, rule))
+        })
+}
+
+/// Converts the labels of a domain rule to punycode, wildcards are kept.
+fn normalize_domain(rule: &str) -> String {
+    rule.trim()
+        .trim_end_matches('.')
+        .split('.')
+        .map(|label| {
+            if label.contains('*') {
+                label.to_ascii_lowercase()
+            } else {
+                idna::domain_to_ascii(label).unwrap_or_else(|_| label.to_ascii_lowercase())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Matches the value against a pattern in which `*` matches any sequence of characters.
+fn glob(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob() {
+        assert!(glob("example.com", "example.com"));
+        assert!(!glob("example.com", "shop.example.com"));
+        assert!(glob("*.example.com", "shop.example.com"));
+        assert!(!glob("*.example.com", "example.com"));
+        assert!(glob(
+            "did:web:*.fraud.example*",
+            "did:web:shop.fraud.example:alice"
+        ));
+        assert!(glob("*", "anything"));
+        assert!(!glob("a*b*c", "acb"));
+    }
+
+    #[test]
+    fn test_match() {
+        let lists = Lists::from_json(
+            r#"{
+                "allow": {"domains": ["*.partner.example", "bücher.example"], "dids": ["did:web:partner.example"]},
+                "block": {"domains": ["fraud.partner.example"], "issuers": ["did:example:*"]}
+            }"#,
+        )
+        .unwrap();
+
+        let blocked = lists.match_domain("fraud.partner.example").unwrap();
+        assert_eq!(blocked.action, ListAction::Block);
+        assert_eq!(blocked.rule, "fraud.partner.example");
+        assert_eq!(blocked.result.unwrap().payload().code, 1 << 20);
+
+        let allowed = lists.match_domain("shop.partner.example").unwrap();
+        assert_eq!(allowed.action, ListAction::Allow);
+        assert_eq!(allowed.rule, "*.partner.example");
+        assert!(allowed.result.is_none());
+        assert!(lists.match_domain("xn--bcher-kva.example").is_some());
+        assert!(lists.match_domain("shop.example").is_none());
+
+        assert_eq!(
+            lists.match_did("did:web:partner.example").unwrap().list,
+            ListKind::Did
+        );
+        assert_eq!(
+            lists.match_issuer("did:example:issuer").unwrap().action,
+            ListAction::Block
+        );
+        assert!(lists.match_issuer("did:web:issuer.example").is_none());
+
+        assert!(Lists::from_json(r#"{"deny": {}}"#).is_err());
+    }
+
+    #[test]
+    fn test_reload() {
+        let path = std::env::temp_dir().join(format!("lists-{}.json", std::process::id()));
+        fs::write(&path, r#"{"block": {"domains": ["fraud.example"]}}"#).unwrap();
+        let lists = AccessLists::load(&path).unwrap();
+        let snapshot = lists.snapshot();
+        assert!(snapshot.match_domain("fraud.example").is_some());
+
+        fs::write(&path, r#"{"block": {"domains": ["other.example"]}}"#).unwrap();
+        lists.reload(&path).unwrap();
+        assert!(lists.snapshot().match_domain("fraud.example").is_none());
+        assert_ne!(lists.snapshot().fingerprint(), snapshot.fingerprint());
+        // requests keep the lists they started with
+        assert!(snapshot.match_domain("fraud.example").is_some());
+
+        // invalid lists are rejected, the previous lists stay in effect
+        fs::write(&path, "{").unwrap();
+        assert!(lists.reload(&path).is_err());
+        assert!(lists.snapshot().match_domain("other.example").is_some());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+use axum::Json;
+use utoipa::OpenApi;
+
+use crate::{diagnostics, verifications};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Verification Service",
+        description = "Verification Service",
+        license(name = "Apache-2.0"),
+        contact(name = "identinet GmbH", url = "https://identinet.io", email = "support@identinet.io"),
+    ),
+    servers(
+        (url = "http://localhost:3012", description = "Local development server"),
+        (url = "https://api.check.identinet.io", description = "Demo deployment"),
+    ),
+    paths(
+        verifications::controller::verify_domain,
+        verifications::controller::verify_domain_grouped,
+        verifications::controller::verify_dry_run,
+        diagnostics::controller::diagnose_domain,
+    )
+)]
+pub struct ApiDoc;
+
+/// Returns the OpenAPI specification of the service.
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Comment that is prepended to the generated `openapi.yaml`.
+    const YAML_HEADER: &str = concat!(
+        "# Generated from code, do not edit. Update with: UPDATE_OPENAPI=1 cargo test openapi\n",
+        "# OpenAPI Specification Explained: https://learn.openapis.org/specification/\n",
+        "# Documentation: https://spec.openapis.org/oas/v3.1.1.html\n",
+        "# Graphical editor and test service: https://editor-next.swagger.io/\n",
+    );
+
+    /// Renders the OpenAPI specification as it is committed in `openapi.yaml`.
+    fn openapi_yaml() -> String {
+        let yaml = ApiDoc::openapi()
+            .to_yaml()
+            .expect("OpenAPI specification can be serialized");
+        format!("{YAML_HEADER}{yaml}")
+    }
+
+    /// Fails when the committed `openapi.yaml` and the specification derived from code diverge.
+    #[test]
+    fn openapi_yaml_is_up_to_date() {
+        let generated = openapi_yaml();
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write("openapi.yaml", &generated).unwrap();
+            return;
+        }
+        assert_eq!(
+            include_str!("../openapi.yaml"),
+            generated,
+            "openapi.yaml is outdated, update it with: UPDATE_OPENAPI=1 cargo test openapi"
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ssi::claims::vc::v1::SpecializedJsonCredential;
+use utoipa::ToSchema;
+
+use crate::dto::{GroupedVerificationResponseDto, TrustLevel};
+use crate::identity::{credential_subjects, property};
+
+/// Policies could not be loaded.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum PolicyError {
+    #[error("policies could not be read: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("policy is invalid TOML: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("policy is invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid policy name '{0}', only letters, digits, '-' and '_' are allowed")]
+    Name(String),
+    #[error("{}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        source: Box<PolicyError>,
+    },
+}
+
+/// Requirements of a use case that a verification has to meet in addition to the verification of its credentials,
+/// e.g.
+///
+/// ```toml
+/// description = "Sellers of the marketplace"
+/// minTrustLevel = "domain-linked"
+///
+/// [[credentials]]
+/// type = "BusinessRegistrationCredential"
+/// issuers = ["did:web:registry.example"]
+/// claims = ["legalName", "registrationNumber"]
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Policy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Minimum trust level of the verification.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_trust_level: Option<TrustLevel>,
+    /// Credentials that must be present and valid.
+    #[serde(default)]
+    pub credentials: Vec<CredentialRequirement>,
+    /// Properties of the credential subject that must be present in any of the valid credentials, e.g. `legalName`.
+    #[serde(default)]
+    pub claims: Vec<String>,
+}
+
+/// Credential that must be present and valid. All conditions must be met by the same credential.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
+pub struct CredentialRequirement {
+    /// Type of the credential, e.g. `BusinessRegistrationCredential`. Any type is accepted if absent.
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    /// DIDs of the accepted issuers. Any issuer is accepted if empty.
+    #[serde(default)]
+    pub issuers: Vec<String>,
+    /// Properties of the credential subject that must be present.
+    #[serde(default)]
+    pub claims: Vec<String>,
+}
+
+/// Kind of a policy rule.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum PolicyRuleKind {
+    Claim,
+    Credential,
+    TrustLevel,
+}
+
+/// Outcome of a single rule of a policy.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+#[non_exhaustive]
+pub struct PolicyRuleOutcome {
+    pub kind: PolicyRuleKind,
+    /// Requirement of the rule, e.g. `identity-verified`, `BusinessRegistrationCredential issued by
+    /// did:web:registry.example` or `legalName`.
+    pub requirement: String,
+    pub passed: bool,
+    /// Index of the credential that satisfies the rule, in the order of `credentials` of the verification result.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<usize>,
+}
+
+/// Evaluation of a verification against the policy that has been requested.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+#[non_exhaustive]
+pub struct PolicyEvaluation {
+    /// Name of the policy, i.e. the value of the `policy` query parameter.
+    pub name: String,
+    /// `true` if all rules passed.
+    pub passed: bool,
+    /// Outcome per rule: the minimum trust level, the required credentials and the required claims.
+    pub rules: Vec<PolicyRuleOutcome>,
+}
+
+impl Policy {
+    /// Parses a policy in TOML.
+    #[inline]
+    pub fn from_toml(toml: &str) -> Result<Self, PolicyError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Parses a policy in JSON.
+    #[inline]
+    pub fn from_json(json: &str) -> Result<Self, PolicyError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Evaluates the verification against the policy. The verification only remains verified if all rules passed.
+    #[inline]
+    pub fn apply(&self, name: &str, dto: &mut GroupedVerificationResponseDto) {
+        let credentials = dto
+            .valid_credentials()
+            .map(|(index, _, credential)| (index, credential));
+        let rules = self.evaluate(credentials, dto.trust_level);
+        let passed = rules.iter().all(|rule| rule.passed);
+        dto.verified = dto.verified && passed;
+        dto.policy = Some(PolicyEvaluation {
+            name: name.to_owned(),
+            passed,
+            rules,
+        });
+    }
+
+    /// Evaluates the rules against the valid credentials, given with their index, and the trust level.
+    fn evaluate<'a>(
+        &self,
+        credentials: impl Iterator<Item = (usize, &'a SpecializedJsonCredential)>,
+        trust_level: TrustLevel,
+    ) -> Vec<PolicyRuleOutcome> {
+        let credentials: Vec<_> = credentials
+            .map(|(index, credential)| {
+                (
+                    index,
+                    credential.issuer.id().as_str(),
+                    serde_json::to_value(credential).unwrap_or_default(),
+                )
+            })
+            .collect();
+        let find = |matches: &dyn Fn(&str, &Value) -> bool| {
+            credentials
+                .iter()
+                .find(|(_, issuer, json)| matches(issuer, json))
+                .map(|(index, _, _)| *index)
+        };
+
+        let trust_level_rule = self.min_trust_level.map(|min| PolicyRuleOutcome {
+            kind: PolicyRuleKind::TrustLevel,
+            requirement: serde_json::to_value(min)
+                .ok()
+                .and_then(|value| value.as_str().map(ToOwned::to_owned))
+                .unwrap_or_default(),
+            passed: trust_level >= min,
+            credential: None,
+        });
+        let credential_rules = self.credentials.iter().map(|requirement| {
+            let credential = find(&|issuer, json| requirement.matches(issuer, json));
+            PolicyRuleOutcome {
+                kind: PolicyRuleKind::Credential,
+                requirement: requirement.to_string(),
+                passed: credential.is_some(),
+                credential,
+            }
+        });
+        let claim_rules = self.claims.iter().map(|claim| {
+            let credential = find(&|_, json| has_claim(json, claim));
+            PolicyRuleOutcome {
+                kind: PolicyRuleKind::Claim,
+                requirement: claim.clone(),
+                passed: credential.is_some(),
+                credential,
+            }
+        });
+        trust_level_rule
+            .into_iter()
+            .chain(credential_rules)
+            .chain(claim_rules)
+            .collect()
+    }
+}
+
+impl CredentialRequirement {
+    /// Returns `true` if the credential, given in its JSON representation, meets all conditions.
+    fn matches(&self, issuer: &str, credential: &Value) -> bool {
+        let type_matches = self.type_.as_ref().is_none_or(|required| {
+            let types = match credential.get("type") {
+                Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+                Some(Value::String(type_)) => vec![type_.as_str()],
+                _ => Vec::new(),
+            };
+            // prefixes of compact IRIs are ignored, e.g. `gx:LegalParticipant` matches `LegalParticipant`
+            types.into_iter().any(|type_| {
+                type_ == required
+                    || type_
+                        .rsplit_once(':')
+                        .is_some_and(|(_, local)| local == required)
+            })
+        });
+        type_matches
+            && (self.issuers.is_empty() || self.issuers.iter().any(|accepted| accepted == issuer))
+            && self.claims.iter().all(|claim| has_claim(credential, claim))
+    }
+}
+
+impl core::fmt::Display for CredentialRequirement {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.type_.as_deref().unwrap_or("VerifiableCredential"))?;
+        if !self.issuers.is_empty() {
+            write!(f, " issued by {}", self.issuers.join(" or "))?;
+        }
+        if !self.claims.is_empty() {
+            write!(f, " with {}", self.claims.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns `true` if a subject of the credential contains the property.
+fn has_claim(credential: &Value, claim: &str) -> bool {
+    credential_subjects(credential)
+        .into_iter()
+        .any(|subject| property(subject, &[claim]).is_some_and(|value| !value.is_null()))
+}
+
+/// Policies by name, selected by the `policy` query parameter.
+#[derive(Clone, Debug, Default)]
+pub struct Policies {
+    policies: BTreeMap<String, Policy>,
+}
+
+impl Policies {
+    /// Loads the policies from the `*.toml` and `*.json` files of the directory. The name of a policy is the name of
+    /// its file without extension, e.g. `marketplace.toml` is selected with `policy=marketplace`.
+    #[inline]
+    pub fn load(directory: &Path) -> Result<Self, PolicyError> {
+        let mut policies = Self::default();
+        let mut paths: Vec<PathBuf> = fs::read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        paths.sort();
+        for path in paths {
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            let parse = match extension {
+                Some("toml") => Policy::from_toml,
+                Some("json") => Policy::from_json,
+                _ => continue,
+            };
+            let name = path
+                .file_stem()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default()
+                .to_owned();
+            let policy = fs::read_to_string(&path)
+                .map_err(PolicyError::from)
+                .and_then(|content| parse(&content))
+                .map_err(|e| PolicyError::File {
+                    path: path.clone(),
+                    source: Box::new(e),
+                })?;
+            policies = policies.with_policy(&name, policy)?;
+        }
+        Ok(policies)
+    }
+
+    /// Adds a policy. Names are restricted to letters, digits, `-` and `_`, as they are part of entity tags.
+    #[inline]
+    pub fn with_policy(mut self, name: &str, policy: Policy) -> Result<Self, PolicyError> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(PolicyError::Name(name.to_owned()));
+        }
+        self.policies.insert(name.to_owned(), policy);
+        Ok(self)
+    }
+
+    /// Returns the policy with the given name.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Policy> {
+        self.policies.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::test_util::credential;
+
+    #[test]
+    fn test_evaluate() {
+        let policy = Policy::from_toml(
+            r#"
+            minTrustLevel = "identity-verified"
+            claims = ["legalName"]
+
+            [[credentials]]
+            type = "BusinessRegistrationCredential"
+            issuers = ["did:web:registry.example"]
+            claims = ["registrationNumber"]
+            "#,
+        )
+        .unwrap();
+        let self_issued = credential(
+            "did:web:shop.example",
+            &["BusinessRegistrationCredential"],
+            serde_json::json!({ "legalName": "Shop GmbH", "registrationNumber": "HRB 1" }),
+        );
+        let registry = credential(
+            "did:web:registry.example",
+            &["gx:BusinessRegistrationCredential"],
+            serde_json::json!({ "registrationNumber": "HRB 1" }),
+        );
+
+        let rules = policy.evaluate(
+            [(0, &self_issued), (2, &registry)].into_iter(),
+            TrustLevel::IdentityVerified,
+        );
+        assert_eq!(rules.len(), 3);
+        assert!(rules.iter().all(|rule| rule.passed));
+        assert_eq!(rules[0].requirement, "identity-verified");
+        assert_eq!(
+            rules[1].requirement,
+            "BusinessRegistrationCredential issued by did:web:registry.example with registrationNumber"
+        );
+        // the self-issued credential doesn't satisfy the issuer of the rule
+        assert_eq!(rules[1].credential, Some(2));
+        assert_eq!(rules[2].kind, PolicyRuleKind::Claim);
+        assert_eq!(rules[2].credential, Some(0));
+
+        let rules = policy.evaluate([(0, &self_issued)].into_iter(), TrustLevel::DomainLinked);
+        assert!(!rules[0].passed);
+        assert!(!rules[1].passed);
+        assert_eq!(rules[1].credential, None);
+        assert!(rules[2].passed);
+
+        // an empty policy has no rules
+        assert!(Policy::default()
+            .evaluate(core::iter::empty(), TrustLevel::None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_load() {
+        let directory = std::env::temp_dir().join(format!("policies-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("blog.json"),
+            r#"{"minTrustLevel": "domain-linked"}"#,
+        )
+        .unwrap();
+        fs::write(
+            directory.join("marketplace.toml"),
+            "[[credentials]]\ntype = \"BusinessRegistrationCredential\"\n",
+        )
+        .unwrap();
+        fs::write(directory.join("README.md"), "ignored").unwrap();
+        let policies = Policies::load(&directory).unwrap();
+        assert_eq!(
+            policies.get("blog").unwrap().min_trust_level,
+            Some(TrustLevel::DomainLinked)
+        );
+        assert_eq!(policies.get("marketplace").unwrap().credentials.len(), 1);
+        assert!(policies.get("README").is_none());
+
+        // unknown rules are rejected, they would silently weaken the policy
+        fs::write(directory.join("typo.toml"), "min_trust_level = \"none\"\n").unwrap();
+        let error = Policies::load(&directory).unwrap_err();
+        assert!(error.to_string().contains("typo.toml"));
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(Policies::default()
+            .with_policy("a\"b", Policy::default())
+            .is_err());
+    }
+}
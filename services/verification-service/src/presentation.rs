@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ssi::claims::{
+    jwt::ToDecodedJwt as _,
+    vc::v1::{decode_jwt_vc_claims, decode_jwt_vp_claims, JsonPresentation},
+    JwsStr,
+};
+use utoipa::ToSchema;
+
+/// Encoding of a presentation.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum PresentationFormat {
+    /// JSON-LD with an embedded proof, e.g. a Data Integrity proof.
+    #[default]
+    Ldp,
+    /// JSON Web Token, see <https://www.w3.org/TR/vc-data-model/#json-web-token>.
+    Jwt,
+}
+
+/// Presentation that has been served by a Linked Verifiable Presentation endpoint.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum EncodedPresentation {
+    /// Presentation with an embedded proof.
+    Ldp(JsonPresentation),
+    /// Presentation in JWT form and its decoded claims. Credentials in JWT form have been decoded as well.
+    Jwt(String, JsonPresentation),
+}
+
+impl EncodedPresentation {
+    #[inline]
+    #[must_use]
+    pub const fn format(&self) -> PresentationFormat {
+        match self {
+            Self::Ldp(_) => PresentationFormat::Ldp,
+            Self::Jwt(..) => PresentationFormat::Jwt,
+        }
+    }
+
+    /// Returns the presentation, decoded if it has been encoded as JWT.
+    #[inline]
+    #[must_use]
+    pub const fn presentation(&self) -> &JsonPresentation {
+        match self {
+            Self::Ldp(presentation) | Self::Jwt(_, presentation) => presentation,
+        }
+    }
+}
+
+/// Parses the body of a Linked Verifiable Presentation endpoint, see <https://identity.foundation/linked-vp>.
+///
+/// The body contains a presentation, a presentation in JWT form or an array of both. The body is parsed as JWT if the
+/// media type is `application/jwt` or ends with `+jwt`, e.g. `application/vp+jwt`, and as JSON if it's
+/// `application/json` or ends with `+json`. Otherwise, the encoding is detected from the body. JSON strings contain
+/// presentations in JWT form.
+#[inline]
+pub fn parse_presentations(
+    body: &str,
+    content_type: Option<&str>,
+) -> Result<Vec<EncodedPresentation>, String> {
+    let media_type = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .map(|media_type| media_type.trim().to_ascii_lowercase());
+    let media_type = media_type.as_deref().unwrap_or_default();
+    let is_jwt = media_type == "application/jwt" || media_type.ends_with("+jwt");
+    let is_json = media_type == "application/json" || media_type.ends_with("+json");
+    let body = body.trim();
+    if is_jwt || (!is_json && !body.starts_with(['{', '[', '"'])) {
+        return parse_jwt(body).map(|presentation| vec![presentation]);
+    }
+    match serde_json::from_str(body).map_err(|e| format!("Invalid JSON: {e}"))? {
+        Value::Array(values) => values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| parse_value(value).map_err(|e| format!("Presentation {i}: {e}")))
+            .collect(),
+        value => parse_value(value).map(|presentation| vec![presentation]),
+    }
+}
+
+/// Parses a presentation in JSON or, if the value is a string, in JWT form.
+fn parse_value(value: Value) -> Result<EncodedPresentation, String> {
+    match value {
+        Value::String(jwt) => parse_jwt(jwt.trim()),
+        value => serde_json::from_value(value)
+            .map(EncodedPresentation::Ldp)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Decodes a presentation in JWT form and the credentials in JWT form it contains.
+fn parse_jwt(jwt: &str) -> Result<EncodedPresentation, String> {
+    let mut presentation = decode_jwt_presentation(jwt)?;
+    let credentials = match presentation.get_mut("verifiableCredential") {
+        Some(Value::Array(credentials)) => credentials.iter_mut().collect(),
+        Some(credential) => vec![credential],
+        None => Vec::new(),
+    };
+    for credential in credentials {
+        if let Value::String(jwt) = credential {
+            *credential = decode_jwt_credential(jwt)?;
+        }
+    }
+    serde_json::from_value(presentation)
+        .map(|presentation| EncodedPresentation::Jwt(jwt.to_owned(), presentation))
+        .map_err(|e| e.to_string())
+}
+
+/// Decodes a presentation in JWT form without verifying it. Registered claims are mapped to the properties of the
+/// presentation, e.g. `iss` to `holder`.
+#[inline]
+pub fn decode_jwt_presentation(jwt: &str) -> Result<Value, String> {
+    let jws = JwsStr::new(jwt).map_err(|e| e.to_string())?;
+    let decoded = jws.to_decoded_jwt().map_err(|e| e.to_string())?;
+    decode_jwt_vp_claims(decoded.signing_bytes.payload).map_err(|e| e.to_string())
+}
+
+/// Decodes a credential in JWT form without verifying it. Registered claims are mapped to the properties of the
+/// credential, e.g. `iss` to `issuer` and `sub` to the id of the credential subject.
+#[inline]
+pub fn decode_jwt_credential(jwt: &str) -> Result<Value, String> {
+    let jws = JwsStr::new(jwt).map_err(|e| e.to_string())?;
+    let decoded = jws.to_decoded_jwt().map_err(|e| e.to_string())?;
+    decode_jwt_vc_claims(decoded.signing_bytes.payload).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwt() -> String {
+        serde_json::from_str(include_str!("../tests/presentations/presentation-jwt.json")).unwrap()
+    }
+
+    #[test]
+    fn test_parse_jwt() {
+        let holder = include_str!("../tests/dids/did-holder").trim();
+        for (body, content_type) in [
+            (jwt(), Some("application/vp+jwt")),
+            (
+                format!("{}\n", jwt()),
+                Some("application/jwt; charset=utf-8"),
+            ),
+            (jwt(), None),
+            (format!("\"{}\"", jwt()), None),
+            (format!("\"{}\"", jwt()), Some("application/json")),
+        ] {
+            let presentations = parse_presentations(&body, content_type).unwrap();
+            assert_eq!(presentations.len(), 1, "{content_type:?}");
+            let presentation = &presentations[0];
+            assert_eq!(presentation.format(), PresentationFormat::Jwt);
+            assert_eq!(
+                presentation
+                    .presentation()
+                    .holder
+                    .as_ref()
+                    .map(|holder| holder.as_str()),
+                Some(holder)
+            );
+            // the credential in JWT form has been decoded
+            let credentials = &presentation.presentation().verifiable_credentials;
+            assert_eq!(credentials.len(), 2);
+            assert_eq!(
+                credentials[0].issuer.id().as_str(),
+                include_str!("../tests/dids/did-trust-party").trim()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_array() {
+        let body = format!(
+            "[{}, \"{}\"]",
+            include_str!("../tests/presentations/presentation-single-vc.json"),
+            jwt()
+        );
+        let presentations = parse_presentations(&body, Some("application/json")).unwrap();
+        assert_eq!(presentations.len(), 2);
+        assert_eq!(presentations[0].format(), PresentationFormat::Ldp);
+        assert_eq!(presentations[1].format(), PresentationFormat::Jwt);
+
+        assert!(parse_presentations("[]", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        // a JWT with a JSON media type isn't accepted
+        assert!(parse_presentations(&jwt(), Some("application/json")).is_err());
+        assert!(parse_presentations("<html></html>", Some("text/html")).is_err());
+        assert!(parse_presentations("a.b.c", Some("application/vp+jwt")).is_err());
+        let error = parse_presentations("[{}, 1]", None).unwrap_err();
+        assert!(error.starts_with("Presentation 0: "), "{error}");
+    }
+}
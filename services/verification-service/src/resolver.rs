@@ -0,0 +1,353 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use ssi::claims::chrono::DateTime;
+use ssi::dids::{
+    resolution::{Error, Options, Output},
+    AnyDidMethod, DIDBuf, DIDResolver, Document, StaticDIDResolver, DID,
+};
+use url::Url;
+use utoipa::ToSchema;
+
+use crate::fetch::{self, FetchError, Fetcher};
+
+/// DID resolver that is used during the verification.
+///
+/// DID documents that have been registered via [`DidResolver::with_document`] take precedence, e.g. documents that
+/// haven't been published yet. did:web documents are downloaded with the fetcher registered via
+/// [`DidResolver::with_fetcher`], so the limits of outbound requests apply. All other DIDs are resolved via
+/// [`AnyDidMethod`].
+#[derive(Clone, Default)]
+pub struct DidResolver {
+    documents: Arc<StaticDIDResolver>,
+    metadata: Arc<HashMap<DIDBuf, DocumentMetadata>>,
+    fetcher: Option<Fetcher>,
+}
+
+/// DID document metadata, see <https://www.w3.org/TR/did-core/#did-document-metadata>.
+///
+/// Properties are only present if the DID method or the registered document provides them. For did:web documents,
+/// `updated` and `versionId` are taken from the `Last-Modified` and `ETag` headers of the HTTP response.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct DocumentMetadata {
+    /// Timestamp of the creation of the DID, e.g. `2024-01-01T00:00:00Z`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// `true` if the DID has been deactivated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deactivated: Option<bool>,
+    /// Timestamp of the next scheduled update of the DID document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_update: Option<String>,
+    /// Version of the next scheduled update of the DID document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_version_id: Option<String>,
+    /// Timestamp of the last update of the DID document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+    /// Version of the resolved DID document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_id: Option<String>,
+}
+
+impl DocumentMetadata {
+    /// Returns `true` if the DID has been deactivated.
+    #[inline]
+    #[must_use]
+    pub fn is_deactivated(&self) -> bool {
+        self.deactivated == Some(true)
+    }
+}
+
+/// DID resolution metadata, see <https://www.w3.org/TR/did-core/#did-resolution-metadata>.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ResolutionMetadata {
+    /// Media type of the resolved representation, e.g. `application/did+json`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+}
+
+/// Resolved DID document and its metadata.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Resolution {
+    pub document: Document,
+    pub document_metadata: DocumentMetadata,
+    pub resolution_metadata: ResolutionMetadata,
+}
+
+impl DidResolver {
+    /// Registers a DID document that is returned instead of resolving the DID.
+    #[inline]
+    #[must_use]
+    pub fn with_document(mut self, did: DIDBuf, document: Output<Vec<u8>>) -> Self {
+        Arc::make_mut(&mut self.documents).insert(did, document);
+        self
+    }
+
+    /// Registers the metadata of a DID document, e.g. to test how deactivated DIDs are treated. The metadata takes
+    /// precedence over the metadata that is returned by the DID method.
+    #[inline]
+    #[must_use]
+    pub fn with_document_metadata(mut self, did: DIDBuf, metadata: DocumentMetadata) -> Self {
+        Arc::make_mut(&mut self.metadata).insert(did, metadata);
+        self
+    }
+
+    /// Resolves the DID document and keeps the metadata that is returned alongside it.
+    ///
+    /// DID methods only report whether the DID has been deactivated. For did:web documents that are downloaded with
+    /// the fetcher, `updated` and `versionId` are derived from the `Last-Modified` and `ETag` headers.
+    #[inline]
+    pub async fn resolve_with_metadata(&self, did: &DID) -> Result<Resolution, Error> {
+        let (output, document_metadata) = self
+            .resolve_representation_with_metadata(did, Options::default())
+            .await?;
+        let content_type = output.metadata.content_type.clone();
+        let media_type = content_type
+            .as_deref()
+            .ok_or(Error::NoRepresentation)?
+            .parse()?;
+        let document = Document::from_bytes(media_type, &output.document)
+            .map_err(Error::InvalidData)?
+            .into_document();
+        let document_metadata = self.metadata.get(did).cloned().unwrap_or(document_metadata);
+        Ok(Resolution {
+            document,
+            document_metadata,
+            resolution_metadata: ResolutionMetadata { content_type },
+        })
+    }
+
+    /// Downloads did:web documents with the given fetcher.
+    #[inline]
+    #[must_use]
+    pub fn with_fetcher(mut self, fetcher: Fetcher) -> Self {
+        self.fetcher = Some(fetcher);
+        self
+    }
+
+    /// Resolves the DID document like [`DIDResolver::resolve_representation`] and returns its metadata.
+    async fn resolve_representation_with_metadata(
+        &self,
+        did: &DID,
+        options: Options,
+    ) -> Result<(Output<Vec<u8>>, DocumentMetadata), Error> {
+        let output = match self
+            .documents
+            .resolve_representation(did, options.clone())
+            .await
+        {
+            Err(Error::NotFound) => match &self.fetcher {
+                Some(fetcher) if did.method_name() == "web" => {
+                    return Self::resolve_did_web(fetcher, did).await;
+                }
+                _ => {
+                    AnyDidMethod::default()
+                        .resolve_representation(did, options)
+                        .await?
+                }
+            },
+            result => result?,
+        };
+        let metadata = DocumentMetadata {
+            deactivated: output.document_metadata.deactivated,
+            ..DocumentMetadata::default()
+        };
+        Ok((output, metadata))
+    }
+
+    /// Downloads the did:web document with the fetcher. The validators of the HTTP response are the only version
+    /// information of did:web documents.
+    async fn resolve_did_web(
+        fetcher: &Fetcher,
+        did: &DID,
+    ) -> Result<(Output<Vec<u8>>, DocumentMetadata), Error> {
+        let url =
+            didweb_to_url(did).ok_or_else(|| Error::InvalidMethodSpecificId(did.to_string()))?;
+        match fetcher.fetch_document(&url).await {
+            Ok(document) => {
+                let metadata = did_web_document_metadata(&document);
+                let output = Output::from_content(
+                    document.body.into_bytes(),
+                    Some("application/did+json".to_owned()),
+                );
+                Ok((output, metadata))
+            }
+            Err(FetchError::NotFound(_)) => Err(Error::NotFound),
+            Err(e) => Err(Error::internal(e)),
+        }
+    }
+}
+
+/// Derives the metadata of a did:web document from the validators of its HTTP response.
+fn did_web_document_metadata(document: &fetch::Document) -> DocumentMetadata {
+    DocumentMetadata {
+        updated: document.last_modified.as_deref().and_then(http_date_to_xml),
+        version_id: document
+            .etag
+            .as_deref()
+            .map(|etag| etag.trim_start_matches("W/").trim_matches('"').to_owned()),
+        ..DocumentMetadata::default()
+    }
+}
+
+/// Converts an HTTP date, e.g. `Wed, 01 Jan 2025 00:00:00 GMT`, to the XML Schema dateTime in UTC that DID document
+/// metadata uses, e.g. `2025-01-01T00:00:00Z`.
+fn http_date_to_xml(date: &str) -> Option<String> {
+    DateTime::parse_from_rfc2822(date)
+        .ok()
+        .map(|date| date.to_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string())
+}
+
+impl DIDResolver for DidResolver {
+    #[inline]
+    async fn resolve_representation<'a>(
+        &'a self,
+        did: &'a DID,
+        options: Options,
+    ) -> Result<Output<Vec<u8>>, Error> {
+        self.resolve_representation_with_metadata(did, options)
+            .await
+            .map(|(output, _)| output)
+    }
+}
+
+/// Constructs the URL of the DID document for did:web DIDs. `None` is returned for all other DID methods.
+/// <https://w3c-ccg.github.io/did-method-web/#read-resolve>
+#[inline]
+#[must_use]
+pub fn didweb_to_url(did: &DID) -> Option<Url> {
+    let id = did.as_str().strip_prefix("did:web:")?;
+    let mut parts = id.split(':');
+    let authority = parts.next()?.replace("%3A", ":");
+    let path = parts.collect::<Vec<_>>();
+    let path = if path.is_empty() {
+        ".well-known".to_owned()
+    } else {
+        path.join("/")
+    };
+    Url::parse(&format!("https://{authority}/{path}/did.json")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_didweb_to_url() {
+        let url = |did: &str| {
+            didweb_to_url(&DIDBuf::from_string(did.to_owned()).unwrap()).map(String::from)
+        };
+        assert_eq!(
+            url("did:web:example.com").as_deref(),
+            Some("https://example.com/.well-known/did.json")
+        );
+        assert_eq!(
+            url("did:web:example.com%3A3000").as_deref(),
+            Some("https://example.com:3000/.well-known/did.json")
+        );
+        assert_eq!(
+            url("did:web:example.com:user:alice").as_deref(),
+            Some("https://example.com/user/alice/did.json")
+        );
+        assert_eq!(
+            url("did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_did_web_with_fetcher() {
+        let document =
+            r#"{"@context": "https://www.w3.org/ns/did/v1", "id": "did:web:example.com"}"#;
+        let fetcher = Fetcher::from_documents(HashMap::from([(
+            Url::parse("https://example.com/.well-known/did.json").unwrap(),
+            document.to_owned(),
+        )]));
+        let resolver = DidResolver::default().with_fetcher(fetcher);
+
+        let did = DIDBuf::from_string("did:web:example.com".to_owned()).unwrap();
+        let output = resolver.resolve(&did).await.unwrap();
+        assert_eq!(output.document.id.as_str(), "did:web:example.com");
+
+        let resolution = resolver.resolve_with_metadata(&did).await.unwrap();
+        assert_eq!(resolution.document.id.as_str(), "did:web:example.com");
+        assert!(!resolution.document_metadata.is_deactivated());
+        // documents served from memory have no validators
+        assert_eq!(resolution.document_metadata, DocumentMetadata::default());
+        assert_eq!(
+            resolution.resolution_metadata.content_type.as_deref(),
+            Some("application/did+json")
+        );
+
+        let unknown = DIDBuf::from_string("did:web:unknown.example.com".to_owned()).unwrap();
+        assert!(matches!(
+            resolver.resolve(&unknown).await,
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_did_web_document_metadata() {
+        let document = fetch::Document {
+            body: String::new(),
+            content_type: None,
+            etag: Some("W/\"42\"".to_owned()),
+            last_modified: Some("Wed, 01 Jan 2025 10:20:30 GMT".to_owned()),
+        };
+        let metadata = did_web_document_metadata(&document);
+        assert_eq!(metadata.updated.as_deref(), Some("2025-01-01T10:20:30Z"));
+        assert_eq!(metadata.version_id.as_deref(), Some("42"));
+        assert!(metadata.deactivated.is_none());
+
+        let document = fetch::Document {
+            last_modified: Some("yesterday".to_owned()),
+            etag: None,
+            ..document
+        };
+        assert_eq!(
+            did_web_document_metadata(&document),
+            DocumentMetadata::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_deactivated_did() {
+        let did = DIDBuf::from_string("did:web:example.com".to_owned()).unwrap();
+        let document = Output::new(
+            br#"{"@context": "https://www.w3.org/ns/did/v1", "id": "did:web:example.com"}"#
+                .to_vec(),
+            ssi::dids::document::Metadata {
+                deactivated: Some(true),
+            },
+            ssi::dids::resolution::Metadata::from_content_type(Some(
+                "application/did+json".to_owned(),
+            )),
+        );
+        let resolver = DidResolver::default().with_document(did.clone(), document);
+        let resolution = resolver.resolve_with_metadata(&did).await.unwrap();
+        assert!(resolution.document_metadata.is_deactivated());
+
+        // registered metadata takes precedence
+        let resolver = resolver.with_document_metadata(
+            did.clone(),
+            DocumentMetadata {
+                version_id: Some("2".to_owned()),
+                ..DocumentMetadata::default()
+            },
+        );
+        let resolution = resolver.resolve_with_metadata(&did).await.unwrap();
+        assert!(!resolution.document_metadata.is_deactivated());
+        assert_eq!(
+            resolution.document_metadata.version_id.as_deref(),
+            Some("2")
+        );
+    }
+}
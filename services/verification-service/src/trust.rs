@@ -0,0 +1,135 @@
+use crate::dto::{GroupedVerificationResponseDto, TrustLevel};
+
+/// Configuration of the trust level that is derived from a verification.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct TrustConfig {
+    /// DIDs of issuers whose credentials raise the trust level to [`TrustLevel::TrustedIssuerVerified`].
+    pub trusted_issuers: Vec<String>,
+}
+
+impl TrustConfig {
+    /// Trusts the credentials of the given issuers.
+    #[inline]
+    #[must_use]
+    pub fn with_trusted_issuers(mut self, trusted_issuers: Vec<String>) -> Self {
+        self.trusted_issuers = trusted_issuers;
+        self
+    }
+
+    /// Derives the trust level from the checks that passed.
+    ///
+    /// The levels build on each other: an origin that is linked to an active DID is [`TrustLevel::DomainLinked`]. If
+    /// all credentials are valid and at least one of them has been issued by a third party, the level is
+    /// [`TrustLevel::IdentityVerified`], or [`TrustLevel::TrustedIssuerVerified`] if the issuer is trusted. DIDs that
+    /// have been verified directly skip the domain-linked level.
+    #[inline]
+    #[must_use]
+    pub fn trust_level(&self, dto: &GroupedVerificationResponseDto) -> TrustLevel {
+        let issuers: Vec<&str> = dto
+            .valid_credentials()
+            .map(|(_, holder, credential)| (holder, credential.issuer.id().as_str()))
+            .filter(|(holder, issuer)| issuer != holder)
+            .map(|(_, issuer)| issuer)
+            .collect();
+
+        let domain_linked = dto.origin.is_some() && dto.dids.iter().any(|did| did.result.is_none());
+        if !dto.verified || issuers.is_empty() {
+            return if domain_linked {
+                TrustLevel::DomainLinked
+            } else {
+                TrustLevel::None
+            };
+        }
+        if issuers
+            .iter()
+            .any(|issuer| self.trusted_issuers.iter().any(|trusted| trusted == issuer))
+        {
+            TrustLevel::TrustedIssuerVerified
+        } else {
+            TrustLevel::IdentityVerified
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::test_util::{credential, did_verification, grouped_dto};
+    use crate::dto::VerificationResult;
+
+    const HOLDER: &str = "did:web:shop.example";
+    const ISSUER: &str = "did:web:registry.example";
+
+    /// Verification of the holder DID with valid credentials of the given issuers.
+    fn dto(origin: Option<&str>, issuers: &[&str]) -> GroupedVerificationResponseDto {
+        let credentials = issuers
+            .iter()
+            .map(|issuer| {
+                let subject = serde_json::json!({ "id": HOLDER });
+                (
+                    credential(issuer, &[], subject),
+                    VerificationResult::vc_valid(),
+                )
+            })
+            .collect();
+        grouped_dto(origin, vec![did_verification(HOLDER, credentials)])
+    }
+
+    #[test]
+    fn test_trust_level() {
+        let config = TrustConfig::default();
+        let origin = Some("https://shop.example");
+        assert_eq!(
+            config.trust_level(&dto(origin, &[ISSUER])),
+            TrustLevel::IdentityVerified
+        );
+        assert_eq!(
+            config.trust_level(&dto(origin, &[])),
+            TrustLevel::DomainLinked
+        );
+
+        let trusted = TrustConfig::default().with_trusted_issuers(vec![ISSUER.to_owned()]);
+        assert_eq!(
+            trusted.trust_level(&dto(origin, &[HOLDER, ISSUER])),
+            TrustLevel::TrustedIssuerVerified
+        );
+        // credentials of trusted issuers don't count if a DID has been deactivated
+        let mut deactivated = dto(origin, &[ISSUER]);
+        deactivated.dids[0].result = Some(VerificationResult::did_deactivated(HOLDER.to_owned()));
+        assert_eq!(trusted.trust_level(&deactivated), TrustLevel::None);
+    }
+
+    #[test]
+    fn test_trust_level_self_issued() {
+        // self-issued credentials don't verify the identity
+        let config = TrustConfig::default().with_trusted_issuers(vec![HOLDER.to_owned()]);
+        assert_eq!(
+            config.trust_level(&dto(Some("https://shop.example"), &[HOLDER])),
+            TrustLevel::DomainLinked
+        );
+        assert_eq!(config.trust_level(&dto(None, &[HOLDER])), TrustLevel::None);
+    }
+
+    #[test]
+    fn test_trust_level_did() {
+        // DIDs that have been verified directly skip the domain-linked level
+        let config = TrustConfig::default();
+        assert_eq!(config.trust_level(&dto(None, &[])), TrustLevel::None);
+        assert_eq!(
+            config.trust_level(&dto(None, &[ISSUER])),
+            TrustLevel::IdentityVerified
+        );
+    }
+
+    #[test]
+    fn test_trust_level_not_verified() {
+        // a failed check keeps the level at the linked origin, even with valid third-party credentials
+        let config = TrustConfig::default().with_trusted_issuers(vec![ISSUER.to_owned()]);
+        let mut dto = dto(Some("https://shop.example"), &[ISSUER]);
+        dto.verified = false;
+        assert_eq!(config.trust_level(&dto), TrustLevel::DomainLinked);
+        dto.origin = None;
+        assert_eq!(config.trust_level(&dto), TrustLevel::None);
+    }
+}
@@ -0,0 +1,438 @@
+use core::{future::Future, time::Duration};
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use ssi::claims::chrono::Utc;
+use tokio::sync::OnceCell;
+use url::Url;
+use verification_service::cache::{CacheBackend, MemoryBackend};
+use verification_service::dto::{GroupedVerificationResponseDto, VerificationError};
+
+/// Configuration of the verification cache.
+#[derive(Clone, Debug)]
+pub struct CacheConfig {
+    /// Maximum time a verification result is reused. The cache is disabled if zero.
+    pub ttl: Duration,
+    /// Time a failed verification is reused, at most `ttl`.
+    pub negative_ttl: Duration,
+    /// Maximum size of the in-memory cache in bytes.
+    pub max_size: u64,
+    /// Backend that is shared with other replicas or survives restarts. Verifications are cached in memory if unset.
+    pub backend: Option<Arc<dyn CacheBackend>>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(300),
+            negative_ttl: Duration::from_secs(30),
+            max_size: 64 * 1024 * 1024,
+            backend: None,
+        }
+    }
+}
+
+/// Outcome of a verification that is shared by all requests for the same origin or DID.
+#[derive(Serialize, Deserialize)]
+pub struct CachedVerification {
+    pub outcome: Result<GroupedVerificationResponseDto, VerificationError>,
+    /// Point in time when the outcome must be verified again.
+    pub expires_at: SystemTime,
+    /// Hash of the verification result, used as entity tag. Failures have no entity tag.
+    pub etag: Option<String>,
+}
+
+impl CachedVerification {
+    fn new(
+        outcome: Result<GroupedVerificationResponseDto, VerificationError>,
+        config: &CacheConfig,
+    ) -> Self {
+        let ttl = match &outcome {
+            Ok(dto) => {
+                time_until_first_expiry(dto).map_or(config.ttl, |expiry| expiry.min(config.ttl))
+            }
+            Err(_) => config.negative_ttl.min(config.ttl),
+        };
+        let etag = outcome.as_ref().ok().and_then(|dto| {
+            let json = serde_json::to_string(dto).ok()?;
+            let mut hasher = DefaultHasher::new();
+            json.hash(&mut hasher);
+            Some(format!("{:016x}", hasher.finish()))
+        });
+        Self {
+            outcome,
+            expires_at: SystemTime::now() + ttl,
+            etag,
+        }
+    }
+
+    /// Returns a copy of the verification that has been adjusted for a single request, e.g. evaluated against a
+    /// policy. The tag distinguishes the entity tag from the one of the shared verification.
+    pub fn adjusted(
+        &self,
+        tag: &str,
+        adjust: impl FnOnce(&mut GroupedVerificationResponseDto),
+    ) -> Self {
+        let outcome = self.outcome.clone().map(|mut dto| {
+            adjust(&mut dto);
+            dto
+        });
+        Self {
+            outcome,
+            expires_at: self.expires_at,
+            etag: self.etag.as_ref().map(|etag| format!("{etag}-{tag}")),
+        }
+    }
+
+    /// Remaining time the outcome may be reused.
+    pub fn max_age(&self) -> Duration {
+        self.expires_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default()
+    }
+}
+
+/// Returns the time until the first credential expires. Credentials that have expired already are ignored as their
+/// verification result doesn't change anymore.
+fn time_until_first_expiry(dto: &GroupedVerificationResponseDto) -> Option<Duration> {
+    let now = Utc::now();
+    dto.credentials()
+        .filter_map(|credential| credential.credential.expiration_date.as_ref())
+        .map(|date| date.to_value().latest().to_utc())
+        .filter(|date| *date > now)
+        .min()
+        .and_then(|date| (date - now).to_std().ok())
+}
+
+/// Verification that is in progress, awaited by all requests for the same key.
+type InFlight = Arc<OnceCell<Arc<CachedVerification>>>;
+
+/// Cache of verification outcomes.
+///
+/// Entries are keyed by the normalized query and the time bucket of the evaluation, so results aren't reused beyond
+/// the bucket even if the clock of an entry hasn't run out. Concurrent requests for the same key within a replica wait
+/// for a single verification. Failures of the backend are treated like missing entries.
+#[derive(Clone)]
+pub struct VerificationCache {
+    config: CacheConfig,
+    backend: Option<Arc<dyn CacheBackend>>,
+    in_flight: Arc<Mutex<HashMap<String, InFlight>>>,
+}
+
+impl VerificationCache {
+    pub fn new(config: CacheConfig) -> Self {
+        let backend = (!config.ttl.is_zero()).then(|| {
+            config.backend.clone().unwrap_or_else(|| {
+                Arc::new(MemoryBackend::new(config.max_size)) as Arc<dyn CacheBackend>
+            })
+        });
+        Self {
+            config,
+            backend,
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Returns the cached outcome for the URL or runs the given verification and caches its outcome. Outcomes are only
+    /// reused for the same version of the configuration that affects them, e.g. the fingerprint of the block lists.
+    pub async fn get_or_verify(
+        &self,
+        url: &Url,
+        version: &str,
+        verify: impl Future<Output = Result<GroupedVerificationResponseDto, VerificationError>>,
+    ) -> Arc<CachedVerification> {
+        let Some(backend) = &self.backend else {
+            return Arc::new(CachedVerification::new(verify.await, &self.config));
+        };
+        let key = self.key(url, version, SystemTime::now());
+        if let Some(verification) = load(backend.as_ref(), &key).await {
+            return verification;
+        }
+
+        let guard = InFlightGuard::new(&self.in_flight, &key);
+        let verification = guard
+            .cell
+            .get_or_init(|| async {
+                // the verification might have finished since the last lookup
+                if let Some(verification) = load(backend.as_ref(), &key).await {
+                    return verification;
+                }
+                let verification = CachedVerification::new(verify.await, &self.config);
+                if let Ok(value) = serde_json::to_vec(&verification) {
+                    let _ = backend.set(&key, value, verification.max_age()).await;
+                }
+                Arc::new(verification)
+            })
+            .await;
+        Arc::clone(verification)
+    }
+
+    /// Returns the key of the URL and configuration version at the given evaluation time.
+    fn key(&self, url: &Url, version: &str, now: SystemTime) -> String {
+        let bucket = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+            / self.config.ttl.as_secs().max(1);
+        format!("verification:{bucket}:{version}:{}", normalize(url))
+    }
+}
+
+/// Request that waits for an in-flight verification. The entry of the verification is removed once it has finished or
+/// the last waiting request has left, even if the requests have been cancelled or the verification panicked.
+struct InFlightGuard<'a> {
+    in_flight: &'a Mutex<HashMap<String, InFlight>>,
+    key: &'a str,
+    cell: InFlight,
+}
+
+impl<'a> InFlightGuard<'a> {
+    fn new(in_flight: &'a Mutex<HashMap<String, InFlight>>, key: &'a str) -> Self {
+        let cell = Arc::clone(
+            in_flight
+                .lock()
+                .expect("in-flight verifications are never poisoned")
+                .entry(key.to_owned())
+                .or_default(),
+        );
+        Self {
+            in_flight,
+            key,
+            cell,
+        }
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        let Ok(mut in_flight) = self.in_flight.lock() else {
+            return;
+        };
+        // waiting requests hold the cell besides the map and this guard, they are registered under the lock
+        if in_flight.get(self.key).is_some_and(|current| {
+            Arc::ptr_eq(current, &self.cell)
+                && (self.cell.initialized() || Arc::strong_count(&self.cell) == 2)
+        }) {
+            in_flight.remove(self.key);
+        }
+    }
+}
+
+/// Returns the unexpired verification of the key from the backend.
+async fn load(backend: &dyn CacheBackend, key: &str) -> Option<Arc<CachedVerification>> {
+    let value = backend.get(key).await.ok()??;
+    let verification: CachedVerification = serde_json::from_slice(&value).ok()?;
+    (verification.expires_at > SystemTime::now()).then(|| Arc::new(verification))
+}
+
+/// Normalizes the query. Query and fragment of a URL aren't considered during the verification, so they don't affect
+/// the key. Paths that are irrelevant for the discovery have been stripped by the caller.
+fn normalize(url: &Url) -> String {
+    match url.scheme() {
+        "did" => url.as_str().to_owned(),
+        _ => url.origin().ascii_serialization() + url.path().trim_end_matches('/'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use verification_service::dto::test_util::{did_verification, grouped_dto};
+    use verification_service::dto::VerificationResult;
+
+    /// Verification of a DID with the given valid credentials in JSON.
+    fn dto(credentials: &[&str]) -> GroupedVerificationResponseDto {
+        let credentials = credentials
+            .iter()
+            .map(|credential| {
+                (
+                    serde_json::from_str(credential).unwrap(),
+                    VerificationResult::vc_valid(),
+                )
+            })
+            .collect();
+        grouped_dto(
+            None,
+            vec![did_verification("did:web:example.com", credentials)],
+        )
+    }
+
+    #[test]
+    fn test_key() {
+        let cache = VerificationCache::new(CacheConfig::default());
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+        let key = |url: &str, now| cache.key(&Url::parse(url).unwrap(), "v1", now);
+        assert_eq!(
+            key("https://example.com", now),
+            "verification:3:v1:https://example.com"
+        );
+        assert_eq!(
+            key("https://EXAMPLE.com:443/?page=1", now),
+            "verification:3:v1:https://example.com"
+        );
+        assert_eq!(
+            key("https://example.com/shops/alice/#top", now),
+            "verification:3:v1:https://example.com/shops/alice"
+        );
+        assert_eq!(
+            key("https://example.com:8443", now),
+            "verification:3:v1:https://example.com:8443"
+        );
+        assert_eq!(
+            key("did:web:example.com", now),
+            "verification:3:v1:did:web:example.com"
+        );
+        assert_eq!(
+            key("https://example.com", now + Duration::from_secs(200)),
+            "verification:4:v1:https://example.com"
+        );
+        // outcomes of other configurations aren't reused
+        assert_ne!(
+            cache.key(&Url::parse("https://example.com").unwrap(), "v2", now),
+            key("https://example.com", now)
+        );
+    }
+
+    #[test]
+    fn test_ttl() {
+        let config = CacheConfig::default();
+        let ttl = |outcome| CachedVerification::new(outcome, &config).max_age();
+
+        assert!(ttl(Ok(dto(&[]))) > Duration::from_secs(290));
+        // expired credentials don't affect the TTL
+        let expired =
+            include_str!("../../tests/credentials/credential-trust-party-issued-expired.json");
+        assert!(ttl(Ok(dto(&[expired]))) > Duration::from_secs(290));
+        assert!(ttl(Err(VerificationError::internal())) <= Duration::from_secs(30));
+
+        let mut credential: serde_json::Value = serde_json::from_str(include_str!(
+            "../../tests/credentials/credential-trust-party-issued-not-expired.json"
+        ))
+        .unwrap();
+        credential["expirationDate"] = (Utc::now() + Duration::from_secs(60)).to_rfc3339().into();
+        let ttl = ttl(Ok(dto(&[&credential.to_string()])));
+        assert!(ttl <= Duration::from_secs(60) && ttl > Duration::from_secs(50));
+    }
+
+    #[tokio::test]
+    async fn test_single_flight() {
+        let cache = VerificationCache::new(CacheConfig::default());
+        let url = Url::parse("https://example.com").unwrap();
+        let verifications = AtomicUsize::new(0);
+        let verify = || async {
+            verifications.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(dto(&[]))
+        };
+
+        let (first, second) = tokio::join!(
+            cache.get_or_verify(&url, "v1", verify()),
+            cache.get_or_verify(&url, "v1", verify())
+        );
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(first.etag.is_some());
+        cache.get_or_verify(&url, "v1", verify()).await;
+        assert_eq!(verifications.load(Ordering::SeqCst), 1);
+
+        // failures are cached as well
+        let other = Url::parse("https://other.example.com").unwrap();
+        let failure = cache
+            .get_or_verify(&other, "v1", async { Err(VerificationError::internal()) })
+            .await;
+        assert!(failure.etag.is_none());
+        cache.get_or_verify(&other, "v1", verify()).await;
+        assert_eq!(verifications.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_single_flight_cancelled() {
+        let cache = VerificationCache::new(CacheConfig::default());
+        let url = Url::parse("https://example.com").unwrap();
+        let in_flight = || cache.in_flight.lock().unwrap().len();
+
+        // a cancelled request doesn't leave its verification behind
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(10),
+            cache.get_or_verify(&url, "v1", core::future::pending()),
+        )
+        .await;
+        assert!(cancelled.is_err());
+        assert_eq!(in_flight(), 0);
+
+        // nor does a panicking verification
+        let panicking = tokio::spawn({
+            let (cache, url) = (cache.clone(), url.clone());
+            async move {
+                cache
+                    .get_or_verify(&url, "v2", async { panic!("verification failed") })
+                    .await
+            }
+        });
+        assert!(panicking.await.is_err());
+        assert_eq!(in_flight(), 0);
+
+        // the verification continues for the requests that are still waiting
+        let (cancelled, verification) = tokio::join!(
+            tokio::time::timeout(
+                Duration::from_millis(10),
+                cache.get_or_verify(&url, "v3", core::future::pending()),
+            ),
+            async {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                let verification = cache.get_or_verify(&url, "v3", async {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok(dto(&[]))
+                });
+                verification.await
+            }
+        );
+        assert!(cancelled.is_err());
+        assert!(verification.outcome.is_ok());
+        assert_eq!(in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_shared_backend() {
+        let backend: Arc<dyn CacheBackend> = Arc::new(MemoryBackend::new(1024 * 1024));
+        let replica = || {
+            VerificationCache::new(CacheConfig {
+                backend: Some(Arc::clone(&backend)),
+                ..CacheConfig::default()
+            })
+        };
+        let url = Url::parse("https://example.com").unwrap();
+        let verifications = AtomicUsize::new(0);
+        let verify = || async {
+            verifications.fetch_add(1, Ordering::SeqCst);
+            Ok(dto(&[]))
+        };
+
+        let first = replica().get_or_verify(&url, "v1", verify()).await;
+        let second = replica().get_or_verify(&url, "v1", verify()).await;
+        assert_eq!(verifications.load(Ordering::SeqCst), 1);
+        assert_eq!(first.etag, second.etag);
+    }
+
+    #[tokio::test]
+    async fn test_disabled() {
+        let cache = VerificationCache::new(CacheConfig {
+            ttl: Duration::ZERO,
+            ..CacheConfig::default()
+        });
+        let url = Url::parse("https://example.com").unwrap();
+        let verifications = AtomicUsize::new(0);
+        for _ in 0..2 {
+            cache
+                .get_or_verify(&url, "v1", async {
+                    verifications.fetch_add(1, Ordering::SeqCst);
+                    Ok(dto(&[]))
+                })
+                .await;
+        }
+        assert_eq!(verifications.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,42 +1,375 @@
 extern crate ssi;
 
+use axum::{
+    extract::State,
+    http::{
+        header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
 use url::Url;
 
+use super::cache::CachedVerification;
 use super::service::{self, ServiceError};
-use verification_service::dto::{VerificationError, VerificationRequest, VerificationResponse};
+use crate::AppState;
+use verification_service::dto::{
+    BriefVerificationResponseDto, DryRunRequest, GroupedVerificationResponse, ProblemDetails,
+    VerificationError, VerificationRequest, VerificationResponse, VerificationResponseDto,
+};
 
+/// Verify DID or URL
+///
+/// Resolves the Decentralized Identifier for the given DNS Domain name or DID and discovers and verifies Linked
+/// Verifiable Presentations and Credentials. Information about the identifier, the linked presentations and
+/// credentials and the verification result are returned.
+///
+/// Related specifications:
+/// - DIF Well-Known DID Configuration <https://identity.foundation/.well-known/resources/did-configuration>
+/// - W3C DID Resolution <https://www.w3.org/TR/did-1.0/#did-resolution>
+/// - DIF Linked Verifiable Presentation <https://identity.foundation/linked-vp>
+#[utoipa::path(
+    get,
+    path = "/v1/verification",
+    operation_id = "getVerification",
+    params(
+        VerificationRequest,
+        ("If-None-Match" = Option<String>, Header, description = "Entity tag of a previously returned result."),
+    ),
+    responses(
+        (status = 200, description = "Verification result, shortened if `brief=true` has been requested.",
+            body = VerificationResponse, headers(
+                ("Cache-Control" = String, description = "Time the result may be reused, capped by the earliest credential expiry."),
+                ("ETag" = String, description = "Entity tag of the result."),
+            )),
+        (status = 304, description = "The result matches the entity tag of `If-None-Match`."),
+        (status = 400, description = "Invalid query or unknown policy.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "DID not found for provided domain name / DID.", body = ProblemDetails,
+            content_type = "application/problem+json"),
+        (status = 422, description = "DID configuration invalid or DID can't be resolved. `code` and `result` contain the failed verification result.",
+            body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal error.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 502, description = "DID resolver failed.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 504, description = "Upstream server didn't respond in time.", body = ProblemDetails,
+            content_type = "application/problem+json"),
+    )
+)]
 pub async fn verify_domain(
+    State(state): State<AppState>,
+    headers: HeaderMap,
     params: VerificationRequest,
-) -> Result<VerificationResponse, VerificationError> {
+) -> Response {
+    let representation = if params.brief {
+        Representation::Brief
+    } else {
+        Representation::Flat
+    };
+    let verification = match verify_with_policy(&state, &params).await {
+        Ok(verification) => verification,
+        Err(error) => return error.into_response(),
+    };
+
+    cached_response(&verification, representation, &headers)
+}
+
+/// Verify DID or URL, grouped per DID and presentation
+///
+/// Like `GET /v1/verification`, but credentials and their results are grouped per DID document, Linked Verifiable
+/// Presentation service and presentation. Every presentation is returned together with the endpoint it has been
+/// fetched from and its own verification result. Services whose endpoints didn't respond are listed without
+/// presentations.
+#[utoipa::path(
+    get,
+    path = "/v2/verification",
+    operation_id = "getGroupedVerification",
+    params(
+        VerificationRequest,
+        ("If-None-Match" = Option<String>, Header, description = "Entity tag of a previously returned result."),
+    ),
+    responses(
+        (status = 200, description = "Verification result, shortened if `brief=true` has been requested.",
+            body = GroupedVerificationResponse, headers(
+                ("Cache-Control" = String, description = "Time the result may be reused, capped by the earliest credential expiry."),
+                ("ETag" = String, description = "Entity tag of the result."),
+            )),
+        (status = 304, description = "The result matches the entity tag of `If-None-Match`."),
+        (status = 400, description = "Invalid query or unknown policy.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "DID not found for provided domain name / DID.", body = ProblemDetails,
+            content_type = "application/problem+json"),
+        (status = 422, description = "DID configuration invalid or DID can't be resolved. `code` and `result` contain the failed verification result.",
+            body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 500, description = "Internal error.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 502, description = "DID resolver failed.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 504, description = "Upstream server didn't respond in time.", body = ProblemDetails,
+            content_type = "application/problem+json"),
+    )
+)]
+pub async fn verify_domain_grouped(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    params: VerificationRequest,
+) -> Response {
+    let representation = if params.brief {
+        Representation::Brief
+    } else {
+        Representation::Grouped
+    };
+    let verification = match verify_with_policy(&state, &params).await {
+        Ok(verification) => verification,
+        Err(error) => return error.into_response(),
+    };
+
+    cached_response(&verification, representation, &headers)
+}
+
+/// Returns the cached verification of the query, evaluated against the requested policy.
+async fn verify_with_policy(
+    state: &AppState,
+    params: &VerificationRequest,
+) -> Result<Arc<CachedVerification>, VerificationError> {
+    let Some(name) = &params.policy else {
+        return Ok(verify_cached(state, params).await);
+    };
+    let policy =
+        state.checks.policies.get(name).ok_or_else(|| {
+            VerificationError::bad_request_from(format!("unknown policy '{name}'"))
+        })?;
+    let verification = verify_cached(state, params).await;
+    // policies are evaluated per request, thus all policies share the cached verification
+    Ok(Arc::new(
+        verification.adjusted(name, |dto| policy.apply(name, dto)),
+    ))
+}
+
+/// Returns the cached verification of the query or verifies it.
+async fn verify_cached(state: &AppState, params: &VerificationRequest) -> Arc<CachedVerification> {
     // safe to unwrap, URL has been parsed during DTO validation already
-    let url = Url::parse(&params.q).unwrap();
+    let url = state.discovery.subject(&Url::parse(&params.q).unwrap());
 
-    let dto = service::verify_by_url(&url)
+    let version = state.checks.lists.snapshot().fingerprint();
+    state
+        .cache
+        .get_or_verify(&url, &version, async {
+            service::verify_by_url(
+                &url,
+                &state.fetcher,
+                &state.resolver,
+                &state.discovery,
+                &state.checks,
+            )
+            .await
+            .map_err(to_verification_error)
+        })
         .await
-        .map_err(|err| match err {
-            ServiceError::UrlNotSupported(s) => VerificationError::bad_request_from(s),
-            ServiceError::ResolutionFailure(error) => match error {
-                ssi::dids::resolution::Error::NotFound => {
-                    VerificationError::not_found_from(error.to_string())
-                }
-                _ => VerificationError::bad_request_from(error.to_string()),
-            },
-            ServiceError::DidConfigInvalid(details) => {
-                VerificationError::verification_impossible_from(details)
+}
+
+/// Representation of a verification result.
+#[derive(Clone, Copy)]
+enum Representation {
+    /// Version 1, credentials and results of all DIDs in flat lists.
+    Flat,
+    /// Verdict and a summary per credential.
+    Brief,
+    /// Version 2, credentials and results grouped per DID and presentation.
+    Grouped,
+}
+
+/// Renders the cached verification with caching headers. `304 Not Modified` is returned if the client's entity tag
+/// matches the result.
+fn cached_response(
+    verification: &CachedVerification,
+    representation: Representation,
+    headers: &HeaderMap,
+) -> Response {
+    let cache_control = format!("public, max-age={}", verification.max_age().as_secs());
+    let dto = match &verification.outcome {
+        Ok(dto) => dto,
+        Err(error) => {
+            return ([(CACHE_CONTROL, cache_control)], error.clone()).into_response();
+        }
+    };
+    // the representations differ, thus each representation has its own entity tag
+    let etag = verification.etag.as_ref().map(|etag| match representation {
+        Representation::Flat => format!("\"{etag}\""),
+        Representation::Brief => format!("\"{etag}-brief\""),
+        Representation::Grouped => format!("\"{etag}-grouped\""),
+    });
+    let not_modified = etag.as_ref().is_some_and(|etag| {
+        headers
+            .get_all(IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate == etag)
+    });
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        match representation {
+            Representation::Flat => {
+                VerificationResponse::OK(Box::new(VerificationResponseDto::from(dto)))
+                    .into_response()
             }
-            _ => VerificationError::bad_request_from("Should not happen".to_owned()),
-        })?;
+            Representation::Brief => VerificationResponse::Brief(
+                BriefVerificationResponseDto::from(&VerificationResponseDto::from(dto)),
+            )
+            .into_response(),
+            Representation::Grouped => {
+                GroupedVerificationResponse::OK(Box::new(dto.clone())).into_response()
+            }
+        }
+    };
+    let response_headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&cache_control) {
+        response_headers.insert(CACHE_CONTROL, value);
+    }
+    if let Some(value) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+        response_headers.insert(ETAG, value);
+    }
+    response
+}
+
+/// Verify unpublished artifacts
+///
+/// Verifies a DID document, DID configuration and Linked Verifiable Presentations before they are published. The
+/// artifacts are served from memory at the locations they will be published at and the regular verification is
+/// executed against the given origin. DIDs that are not part of the bundle, e.g. credential issuers, are resolved as
+/// usual.
+#[utoipa::path(
+    post,
+    path = "/v1/verification/dry-run",
+    operation_id = "postVerificationDryRun",
+    request_body(content((DryRunRequest = "application/json"), (DryRunRequest = "multipart/form-data"))),
+    responses(
+        (status = 200, description = "Verification result, same as for `GET /v1/verification`.", body = VerificationResponseDto),
+        (status = 400, description = "Invalid bundle.", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "DID configuration invalid or DID can't be resolved. `code` and `result` contain the failed verification result.",
+            body = ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn verify_dry_run(
+    State(state): State<AppState>,
+    bundle: DryRunRequest,
+) -> Result<VerificationResponse, VerificationError> {
+    // safe to unwrap, URL has been parsed during DTO validation already
+    let url = Url::parse(&bundle.origin).unwrap();
+
+    let dto = service::verify_dry_run(
+        &url,
+        &bundle,
+        &state.resolver,
+        &state.discovery,
+        &state.checks,
+    )
+    .await
+    .map_err(to_verification_error)?;
+
+    Ok(VerificationResponse::OK(Box::new(
+        VerificationResponseDto::from(&dto),
+    )))
+}
 
-    Ok(VerificationResponse::OK(dto))
+fn to_verification_error(err: ServiceError) -> VerificationError {
+    match err {
+        ServiceError::UrlNotSupported(s) | ServiceError::BundleInvalid(s) => {
+            VerificationError::bad_request_from(s)
+        }
+        ServiceError::ResolutionFailure(error) => VerificationError::resolution_failure(&error),
+        ServiceError::DidConfigInvalid(result) => VerificationError::did_config_invalid(result),
+        ServiceError::UpstreamTimeout(s) => {
+            VerificationError::upstream_timeout().with_internal_detail(s)
+        }
+        ServiceError::Unexpected(s) => VerificationError::internal().with_internal_detail(s),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    // use crate::verifications::dto::VerificationErrorResponseDto;
+    // use crate::verifications::dto::ProblemDetails;
 
-    // use super::*;
     // use axum::{http::StatusCode, routing::get, test_helpers::TestClient, Extension, Router};
 
+    use super::*;
+    use crate::verifications::cache::{CacheConfig, VerificationCache};
+    use verification_service::dto::test_util::grouped_dto;
+    use verification_service::dto::{GroupedVerificationResponseDto, TrustLevel};
+    use verification_service::policy::Policy;
+
+    #[tokio::test]
+    async fn test_cached_response() {
+        let cache = VerificationCache::new(CacheConfig::default());
+        let url = Url::parse("https://example.com").unwrap();
+        let verification = cache
+            .get_or_verify(&url, "v1", async {
+                Ok(GroupedVerificationResponseDto {
+                    verified: false,
+                    ..grouped_dto(None, Vec::new())
+                })
+            })
+            .await;
+
+        let response = cached_response(&verification, Representation::Flat, &HeaderMap::new());
+        assert_eq!(response.status(), StatusCode::OK);
+        let cache_control = response.headers()[CACHE_CONTROL].to_str().unwrap();
+        assert!(cache_control.starts_with("public, max-age="));
+        let etag = response.headers()[ETAG].clone();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, etag.clone());
+        let response = cached_response(&verification, Representation::Flat, &headers);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], etag);
+
+        // the other representations have different entity tags
+        let response = cached_response(&verification, Representation::Brief, &headers);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[ETAG], etag);
+        let response = cached_response(&verification, Representation::Grouped, &headers);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[ETAG], etag);
+
+        let failure = cache
+            .get_or_verify(
+                &Url::parse("https://other.example.com").unwrap(),
+                "v1",
+                async { Err(VerificationError::internal()) },
+            )
+            .await;
+        let response = cached_response(&failure, Representation::Flat, &HeaderMap::new());
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.headers().contains_key(CACHE_CONTROL));
+        assert!(!response.headers().contains_key(ETAG));
+    }
+
+    #[tokio::test]
+    async fn test_policy() {
+        let cache = VerificationCache::new(CacheConfig::default());
+        let verification = cache
+            .get_or_verify(&Url::parse("https://example.com").unwrap(), "v1", async {
+                Ok(GroupedVerificationResponseDto {
+                    trust_level: TrustLevel::DomainLinked,
+                    ..grouped_dto(Some("https://example.com"), Vec::new())
+                })
+            })
+            .await;
+        let policy = Policy::from_toml("minTrustLevel = \"identity-verified\"").unwrap();
+
+        let evaluated =
+            verification.adjusted("marketplace", |dto| policy.apply("marketplace", dto));
+        let dto = evaluated.outcome.as_ref().unwrap();
+        assert!(!dto.verified);
+        let evaluation = dto.policy.as_ref().unwrap();
+        assert_eq!(evaluation.name, "marketplace");
+        assert!(!evaluation.passed);
+        assert_eq!(evaluation.rules.len(), 1);
+        // the shared verification is left untouched and has its own entity tag
+        assert!(verification.outcome.as_ref().unwrap().verified);
+        assert_ne!(evaluated.etag, verification.etag);
+        assert_eq!(evaluated.expires_at, verification.expires_at);
+    }
+
     // FIXME: disable test that performs a network request. Such a test is not permissible in the nix build environment
     // #[tokio::test]
     // async fn test_verify_domain() {
@@ -56,7 +389,7 @@ mod tests {
     //
     //     let res = client.get("/?url=").await;
     //     assert_eq!(res.status(), StatusCode::BAD_REQUEST);
-    //     let data = res.json::<VerificationErrorResponseDto>().await;
+    //     let data = res.json::<ProblemDetails>().await;
     //     assert_eq!(data.error, "empty 'url' param");
     // }
 }
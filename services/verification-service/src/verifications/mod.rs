@@ -1,8 +1,21 @@
-use axum::{routing::get, Router};
+use axum::{
+    routing::{get, post},
+    Router,
+};
 
-mod controller;
-mod service;
+use crate::AppState;
 
-pub fn create_router() -> Router {
-    Router::new().route("/verification", get(controller::verify_domain))
+pub(crate) mod cache;
+pub(crate) mod controller;
+pub(crate) mod service;
+
+pub fn create_router() -> Router<AppState> {
+    Router::new()
+        .route("/verification", get(controller::verify_domain))
+        .route("/verification/dry-run", post(controller::verify_dry_run))
+}
+
+/// Routes of API version 2.
+pub fn create_router_v2() -> Router<AppState> {
+    Router::new().route("/verification", get(controller::verify_domain_grouped))
 }
@@ -0,0 +1,244 @@
+use core::time::Duration;
+
+use ssi::{
+    claims::{
+        chrono::{DateTime, TimeDelta, Utc},
+        vc::v1::{JsonPresentation, SpecializedJsonCredential},
+    },
+    dids::DID,
+};
+
+use crate::dto::Finding;
+
+/// Default period before the expiration of a credential in which a warning is reported.
+pub const DEFAULT_EXPIRY_WINDOW: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Proof types that have been superseded by the Data Integrity cryptosuites, see
+/// <https://www.w3.org/TR/vc-data-integrity/>.
+pub const LEGACY_PROOF_TYPES: [&str; 5] = [
+    "EcdsaSecp256k1Signature2019",
+    "Ed25519Signature2018",
+    "Ed25519Signature2020",
+    "JsonWebSignature2020",
+    "RsaSignature2018",
+];
+
+/// Thresholds of the warnings that are reported alongside the verification results. Warnings never fail a
+/// verification.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct WarningConfig {
+    /// Credentials that expire within this period are reported with `credential.expires_soon`. Disabled if zero.
+    pub expiry_window: Duration,
+    /// Proof types that are reported with `credential.legacy_proof` and `presentation.legacy_proof`.
+    pub legacy_proof_types: Vec<String>,
+}
+
+impl Default for WarningConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            expiry_window: DEFAULT_EXPIRY_WINDOW,
+            legacy_proof_types: LEGACY_PROOF_TYPES.map(str::to_owned).to_vec(),
+        }
+    }
+}
+
+impl WarningConfig {
+    /// Sets the period before the expiration of a credential in which a warning is reported.
+    #[inline]
+    #[must_use]
+    pub const fn with_expiry_window(mut self, expiry_window: Duration) -> Self {
+        self.expiry_window = expiry_window;
+        self
+    }
+
+    /// Returns `true` if the expiration date lies within the expiry window. Expired credentials don't expire soon.
+    #[inline]
+    #[must_use]
+    pub fn expires_soon(&self, expiration: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        let window = TimeDelta::from_std(self.expiry_window).unwrap_or(TimeDelta::MAX);
+        expiration > now && expiration - now < window
+    }
+
+    /// Returns the warnings of a credential of the given holder.
+    ///
+    /// Reported are credentials that expire within the expiry window (`credential.expires_soon`), credentials without
+    /// expiration date (`credential.expiration_missing`), credentials that have been issued by the holder itself
+    /// (`credential.self_issued`) and proofs of a legacy type (`credential.legacy_proof`). Expired credentials are
+    /// failures and therefore not reported.
+    #[inline]
+    #[must_use]
+    pub fn credential_warnings(
+        &self,
+        credential: &SpecializedJsonCredential,
+        holder: &DID,
+        now: DateTime<Utc>,
+    ) -> Vec<Finding> {
+        let json = serde_json::to_value(credential).unwrap_or_default();
+        let resource = json
+            .get("id")
+            .and_then(serde_json::Value::as_str)
+            .map_or_else(|| holder.to_string(), ToOwned::to_owned);
+        let mut warnings = Vec::new();
+
+        match credential
+            .expiration_date
+            .as_ref()
+            .map(|date| date.to_value().latest().to_utc())
+        {
+            Some(expiration) if self.expires_soon(expiration, now) => {
+                warnings.push(Finding::warning(
+                    "credential.expires_soon",
+                    &resource,
+                    format!("Credential expires at {}.", expiration.to_rfc3339()),
+                ));
+            }
+            Some(_) => {}
+            None => warnings.push(Finding::info(
+                "credential.expiration_missing",
+                &resource,
+                "Credential has no expiration date.".to_owned(),
+            )),
+        }
+
+        let issuer = match json.get("issuer") {
+            Some(serde_json::Value::String(issuer)) => Some(issuer.as_str()),
+            Some(issuer) => issuer.get("id").and_then(serde_json::Value::as_str),
+            None => None,
+        };
+        if issuer == Some(holder.as_str()) {
+            warnings.push(Finding::warning(
+                "credential.self_issued",
+                &resource,
+                "Credential has been issued by the holder itself, its claims are not attested by a third party."
+                    .to_owned(),
+            ));
+        }
+
+        warnings.extend(self.legacy_proofs(&json, "credential", &resource));
+        warnings
+    }
+
+    /// Returns the warnings of a presentation, i.e. proofs of a legacy type (`presentation.legacy_proof`).
+    #[inline]
+    #[must_use]
+    pub fn presentation_warnings(
+        &self,
+        presentation: &JsonPresentation,
+        resource: &str,
+    ) -> Vec<Finding> {
+        let json = serde_json::to_value(presentation).unwrap_or_default();
+        self.legacy_proofs(&json, "presentation", resource)
+    }
+
+    /// Reports the proofs of the document that have a legacy type.
+    fn legacy_proofs(
+        &self,
+        json: &serde_json::Value,
+        prefix: &str,
+        resource: &str,
+    ) -> Vec<Finding> {
+        let proofs = match json.get("proof") {
+            Some(serde_json::Value::Array(proofs)) => proofs.iter().collect(),
+            Some(proof) => vec![proof],
+            None => Vec::new(),
+        };
+        proofs
+            .into_iter()
+            .filter_map(|proof| proof.get("type").and_then(serde_json::Value::as_str))
+            .filter(|type_| self.legacy_proof_types.iter().any(|legacy| legacy == type_))
+            .map(|type_| {
+                Finding::warning(
+                    &format!("{prefix}.legacy_proof"),
+                    resource,
+                    format!("Proof type '{type_}' has been superseded by the Data Integrity cryptosuites."),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ssi::dids::DIDBuf;
+
+    use super::*;
+    use crate::dto::Severity;
+
+    fn credential(json: &str) -> SpecializedJsonCredential {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn codes(warnings: &[Finding]) -> Vec<&str> {
+        warnings
+            .iter()
+            .map(|warning| warning.code.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_credential_warnings() {
+        let holder =
+            DIDBuf::from_string(include_str!("../tests/dids/did-holder").trim().to_owned())
+                .unwrap();
+        let config = WarningConfig::default();
+        let not_expired = credential(include_str!(
+            "../tests/credentials/credential-trust-party-issued-not-expired.json"
+        ));
+        let expiration = not_expired
+            .expiration_date
+            .as_ref()
+            .unwrap()
+            .to_value()
+            .latest()
+            .to_utc();
+
+        let warnings =
+            config.credential_warnings(&not_expired, &holder, expiration - TimeDelta::days(365));
+        assert_eq!(codes(&warnings), vec!["credential.legacy_proof"]);
+
+        let warnings =
+            config.credential_warnings(&not_expired, &holder, expiration - TimeDelta::days(5));
+        assert_eq!(
+            codes(&warnings),
+            vec!["credential.expires_soon", "credential.legacy_proof"]
+        );
+        assert_eq!(warnings[0].severity, Severity::Warning);
+
+        // the window is configurable
+        let config = WarningConfig::default().with_expiry_window(Duration::ZERO);
+        let warnings =
+            config.credential_warnings(&not_expired, &holder, expiration - TimeDelta::days(5));
+        assert_eq!(codes(&warnings), vec!["credential.legacy_proof"]);
+
+        let config = WarningConfig {
+            legacy_proof_types: Vec::new(),
+            ..WarningConfig::default()
+        };
+        let no_expiration = credential(include_str!(
+            "../tests/credentials/credential-trust-party-issued-no-expiration-date.json"
+        ));
+        let warnings = config.credential_warnings(&no_expiration, &holder, Utc::now());
+        assert_eq!(codes(&warnings), vec!["credential.expiration_missing"]);
+        assert_eq!(warnings[0].severity, Severity::Info);
+
+        let self_issued = credential(include_str!(
+            "../tests/credentials/credential-self-issued.json"
+        ));
+        let warnings = config.credential_warnings(&self_issued, &holder, Utc::now());
+        assert!(codes(&warnings).contains(&"credential.self_issued"));
+    }
+
+    #[test]
+    fn test_presentation_warnings() {
+        let presentation: JsonPresentation = serde_json::from_str(include_str!(
+            "../tests/presentations/presentation-single-vc.json"
+        ))
+        .unwrap();
+        let warnings = WarningConfig::default()
+            .presentation_warnings(&presentation, "https://example.com/vp.json");
+        assert_eq!(codes(&warnings), vec!["presentation.legacy_proof"]);
+        assert_eq!(warnings[0].resource, "https://example.com/vp.json");
+    }
+}
@@ -292,6 +292,34 @@ cat > presentations/presentation-multiple-vc-bad-subject-id <<EOF
 EOF
 issue_and_encode_vp presentations/presentation-multiple-vc-expired keys/key-holder.jwk "$verification_method_holder"
 
+# Issue a credential and a presentation in JWT form, independent of the configured proof format.
+vc_id="urn:uuid:$(uuidgen)"
+cat > credentials/credential-trust-party-issued-jwt <<EOF
+{
+    "@context": "https://www.w3.org/2018/credentials/v1",
+    "id": "$vc_id",
+    "type": ["VerifiableCredential"],
+    "issuer": "$tp_did",
+    "issuanceDate": "$vc_issuance_date",
+    "credentialSubject": {
+        "id": "$holder_did"
+    }
+}
+EOF
+_issue_and_encode "credential" credentials/credential-trust-party-issued-jwt keys/key-trust-party.jwk "$verification_method_tp" jwt "$vc_proof_type"
+
+vp_id="urn:uuid:"$(uuidgen)
+cat > presentations/presentation-jwt <<EOF
+{
+    "@context": ["https://www.w3.org/2018/credentials/v1"],
+    "id": "$vp_id",
+    "type": ["VerifiablePresentation"],
+    "holder": "$holder_did",
+    "verifiableCredential": [$(cat credentials/credential-trust-party-issued-jwt.json), $(cat credentials/credential-self-issued.json)]
+}
+EOF
+_issue_and_encode "presentation" presentations/presentation-jwt keys/key-holder.jwk "$verification_method_holder" jwt "$vp_proof_type"
+
 
 # Create DID Configurations
 echo '### DID configuration ###'